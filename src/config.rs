@@ -0,0 +1,772 @@
+//! Runtime configuration read from `cua.toml`, either next to `cua.exe` or in
+//! `%ProgramData%\cua`. Every setting is optional and defaults to the built in behaviour.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::events::debounce::DebounceStrategy;
+use crate::events::integrity::INTEGRITY_EVENT_IDS;
+use crate::events::query::{self, EventFilter, QueryList};
+use crate::events::queue::{self, Watcher};
+use crate::state;
+
+pub const CONFIG_FILE_NAME: &str = "cua.toml";
+
+/// Longest debounce window a watcher accepts, events are held back or dropped for this long.
+const MAX_DEBOUNCE_MS: u64 = 60_000;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Directory `cua.log` is written to, defaults to the parent of the directory holding `cua.exe`.
+    pub log_dir: Option<PathBuf>,
+    pub log_rotation: LogRotationConfig,
+    pub logon_logoff: LogonLogoffConfig,
+    pub presence: PresenceConfig,
+    pub failed_logon: FailedLogonConfig,
+    pub audit_integrity: AuditIntegrityConfig,
+    pub audit_policy: AuditPolicyConfig,
+    pub heartbeat: HeartbeatConfig,
+    pub tenant_info: TenantInfoConfig,
+    pub event_queue: EventQueueConfig,
+    /// Also POST records to an HTTP endpoint, off unless a `[webhook]` section is present.
+    pub webhook: Option<WebhookConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogRotationConfig {
+    /// Rotate `cua.log` before it grows past this size, 0 turns size based rotation off.
+    pub max_bytes: u64,
+    /// Also rotate at midnight UTC.
+    pub daily: bool,
+    /// Rotated files kept, the oldest are removed.
+    pub max_files: usize,
+    /// Gzip rotated files.
+    pub compress: bool,
+}
+
+impl Default for LogRotationConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 10 * 1024 * 1024,
+            daily: false,
+            max_files: 10,
+            compress: true,
+        }
+    }
+}
+
+/// Settings every watcher has, set in its own section next to the watcher's filters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatcherConfig {
+    pub channel: String,
+    pub debounce_ms: u64,
+    pub debounce_strategy: DebounceStrategy,
+    /// How far back events are read on the first start, before there is a bookmark.
+    pub look_back_secs: u64,
+    pub select: Vec<SelectConfig>,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            channel: "Security".to_string(),
+            debounce_ms: 0,
+            debounce_strategy: DebounceStrategy::Leading,
+            look_back_secs: 15 * 60,
+            select: Vec::new(),
+        }
+    }
+}
+
+impl WatcherConfig {
+    /// The query reading `xpath` from `channel` and each of the selects.
+    pub fn query(&self, xpath: &str) -> QueryList {
+        self.select.iter().fold(QueryList::channel(&self.channel, xpath), |query, select| {
+            query.select(&select.channel, select.xpath.as_deref().unwrap_or(xpath))
+        })
+    }
+
+    pub fn debounce(&self) -> Option<Duration> {
+        match self.debounce_ms {
+            0 => None,
+            ms => Some(Duration::from_millis(ms))
+        }
+    }
+
+    pub fn look_back(&self) -> Duration {
+        Duration::from_secs(self.look_back_secs)
+    }
+
+    /// Replaces invalid settings with those in `defaults`, `section` is the watcher's section name.
+    fn validate(&mut self, section: &str, defaults: &WatcherConfig, errors: &mut Vec<ConfigError>) {
+        if self.channel.trim().is_empty() {
+            errors.push(ConfigError::Invalid { field: format!("{}.channel", section), reason: "channel is empty".to_string() });
+            self.channel = defaults.channel.clone();
+        }
+        if self.debounce_ms > MAX_DEBOUNCE_MS {
+            errors.push(ConfigError::Invalid { field: format!("{}.debounce_ms", section), reason: format!("{}ms is longer than a minute", self.debounce_ms) });
+            self.debounce_ms = defaults.debounce_ms;
+        }
+        self.select.retain(|select| {
+            let reason = if select.channel.trim().is_empty() {
+                "channel is empty".to_string()
+            } else if select.xpath.as_ref().is_some_and(|xpath| xpath.trim().is_empty()) {
+                format!("query for {} is empty", select.channel)
+            } else {
+                return true;
+            };
+            errors.push(ConfigError::Invalid { field: format!("{}.select", section), reason });
+            false
+        });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogonLogoffConfig {
+    #[serde(flatten)]
+    pub watcher: WatcherConfig,
+    /// Logon types (4624 `LogonType`) recorded, used to build the query when `xpath` isn't set.
+    pub logon_types: Vec<u32>,
+    /// Replaces the query built from `logon_types` entirely.
+    pub xpath: Option<String>,
+    /// SID prefixes that are never recorded, by default DWM and UMDF.
+    pub ignored_sid_prefixes: Vec<String>,
+}
+
+impl Default for LogonLogoffConfig {
+    fn default() -> Self {
+        Self {
+            watcher: WatcherConfig { debounce_ms: 100, ..WatcherConfig::default() },
+            logon_types: vec![2, 7, 10, 11],
+            xpath: None,
+            ignored_sid_prefixes: vec!["S-1-5-96".to_string(), "S-1-5-90".to_string()],
+        }
+    }
+}
+
+/// Lock, unlock and screensaver events (4800-4803), only logged by Windows when
+/// "Audit Other Logon/Logoff Events" is on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresenceConfig {
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub watcher: WatcherConfig,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            watcher: WatcherConfig::default(),
+        }
+    }
+}
+
+/// Failed logons (4625) at the console, over RDP, when unlocking or with cached credentials.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailedLogonConfig {
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub watcher: WatcherConfig,
+    /// Logon types (4625 `LogonType`) recorded.
+    pub logon_types: Vec<u32>,
+}
+
+impl Default for FailedLogonConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            watcher: WatcherConfig::default(),
+            logon_types: vec![2, 7, 10, 11],
+        }
+    }
+}
+
+/// The Security log being cleared (1102), the event log service stopping (1100) and audit
+/// policy changes (4719, 4907).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditIntegrityConfig {
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub watcher: WatcherConfig,
+}
+
+impl Default for AuditIntegrityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            watcher: WatcherConfig::default(),
+        }
+    }
+}
+
+/// Another channel a watcher reads from, a `[[<watcher>.select]]` entry. Each one adds a
+/// `<Select>` to the watcher's query next to its own `channel`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectConfig {
+    pub channel: String,
+    /// XPath filter for this channel, the watcher's own filter if not set.
+    pub xpath: Option<String>,
+}
+
+/// Checks the audit policy the watchers rely on at startup and every `check_interval_secs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditPolicyConfig {
+    pub enabled: bool,
+    pub check_interval_secs: u64,
+}
+
+impl Default for AuditPolicyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            check_interval_secs: 60 * 60,
+        }
+    }
+}
+
+impl AuditPolicyConfig {
+    pub fn check_interval(&self) -> Duration {
+        Duration::from_secs(self.check_interval_secs)
+    }
+}
+
+/// A `heartbeat` record every `interval_secs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 5 * 60,
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+/// `tenant_info` is written when the tenants the device is joined to change, and again after
+/// `refresh_interval_secs` even if they haven't.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TenantInfoConfig {
+    pub refresh_interval_secs: u64,
+}
+
+impl Default for TenantInfoConfig {
+    fn default() -> Self {
+        Self {
+            refresh_interval_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+impl TenantInfoConfig {
+    /// None when only changes are recorded.
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        match self.refresh_interval_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs))
+        }
+    }
+}
+
+/// The queue between the event log's threads and the service loop.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventQueueConfig {
+    /// Events waiting to be recorded, more are dropped until the service catches up.
+    pub capacity: usize,
+}
+
+impl Default for EventQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: queue::DEFAULT_CAPACITY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Extra request headers, e.g. `Authorization`.
+    pub headers: BTreeMap<String, String>,
+    pub batch_size: usize,
+    pub flush_interval_secs: u64,
+    pub timeout_secs: u64,
+    /// Wait between attempts while the endpoint is failing, doubling up to `max_backoff_secs`.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_secs: u64,
+    /// Records that still can't be delivered after retrying are kept here until the endpoint is back.
+    pub spool_dir: Option<PathBuf>,
+    pub spool_max_bytes: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            headers: BTreeMap::new(),
+            batch_size: 50,
+            flush_interval_secs: 5,
+            timeout_secs: 10,
+            initial_backoff_ms: 1_000,
+            max_backoff_secs: 60,
+            spool_dir: None,
+            spool_max_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_secs(self.flush_interval_secs)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn initial_backoff(&self) -> Duration {
+        Duration::from_millis(self.initial_backoff_ms)
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_secs(self.max_backoff_secs)
+    }
+
+    pub fn spool_dir(&self) -> PathBuf {
+        self.spool_dir.clone().unwrap_or_else(|| state::state_dir().join("spool"))
+    }
+}
+
+impl LogonLogoffConfig {
+    pub fn xpath(&self) -> String {
+        self.xpath.clone().unwrap_or_else(|| logon_logoff_xpath(&self.logon_types))
+    }
+}
+
+impl PresenceConfig {
+    pub fn xpath(&self) -> String {
+        EventFilter::ids(&PRESENCE_EVENT_IDS).xpath()
+    }
+}
+
+impl FailedLogonConfig {
+    pub fn xpath(&self) -> String {
+        EventFilter::ids(&[4625]).data_any("LogonType", &self.logon_types).xpath()
+    }
+}
+
+impl AuditIntegrityConfig {
+    pub fn xpath(&self) -> String {
+        EventFilter::ids(&INTEGRITY_EVENT_IDS).xpath()
+    }
+}
+
+/// Workstation locked, unlocked, screensaver invoked and dismissed.
+const PRESENCE_EVENT_IDS: [u32; 4] = [4800, 4801, 4802, 4803];
+
+/// Builds the query for interactive logons (4624 with one of `logon_types`) and logoffs (4647).
+pub fn logon_logoff_xpath(logon_types: &[u32]) -> String {
+    query::any_of(&[EventFilter::ids(&[4624]).data_any("LogonType", logon_types), EventFilter::ids(&[4647])])
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid { field: String, reason: String },
+    /// A key this version doesn't know, e.g. a typo or a setting from a newer version.
+    Unknown(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "Unable to read {} - {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "Unable to parse {} - {}", path.display(), err.message()),
+            ConfigError::Invalid { field, reason } => write!(f, "Invalid {} - {}, using the default", field, reason),
+            ConfigError::Unknown(key) => write!(f, "Ignoring unknown setting {}", key)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The config in use along with where it came from and anything wrong with it. Errors are
+/// kept rather than logged because the log location comes from the config.
+#[derive(Debug, Default)]
+pub struct LoadedConfig {
+    pub config: Config,
+    pub path: Option<PathBuf>,
+    pub errors: Vec<ConfigError>,
+}
+
+impl Config {
+    /// Loads the first config file found next to the exe or in ProgramData.
+    pub fn load() -> LoadedConfig {
+        match search_paths().into_iter().find(|path| path.is_file()) {
+            Some(path) => Config::load_from(&path),
+            None => LoadedConfig::default()
+        }
+    }
+
+    /// Loads a config file, falling back to the defaults if it can't be read or parsed.
+    pub fn load_from(path: &Path) -> LoadedConfig {
+        let parsed = std::fs::read_to_string(path)
+            .map_err(|err| ConfigError::Read(path.to_path_buf(), err))
+            .and_then(|contents| parse(&contents).map_err(|err| ConfigError::Parse(path.to_path_buf(), err)));
+
+        match parsed {
+            Ok((mut config, unknown)) => {
+                let mut errors = unknown.into_iter().map(ConfigError::Unknown).collect::<Vec<ConfigError>>();
+                errors.extend(config.validate());
+                LoadedConfig { config, path: Some(path.to_path_buf()), errors }
+            },
+            Err(err) => LoadedConfig { config: Config::default(), path: Some(path.to_path_buf()), errors: vec![err] }
+        }
+    }
+
+    /// Replaces invalid settings with their defaults and returns what was wrong.
+    pub fn validate(&mut self) -> Vec<ConfigError> {
+        let defaults = LogonLogoffConfig::default();
+        let logon_logoff = &mut self.logon_logoff;
+        let mut errors = Vec::new();
+
+        if let Some(log_dir) = &self.log_dir
+            && !log_dir.is_absolute() {
+            errors.push(ConfigError::Invalid { field: "log_dir".to_string(), reason: format!("{} is not an absolute path", log_dir.display()) });
+            self.log_dir = None;
+        }
+        if self.log_rotation.max_files == 0 {
+            errors.push(ConfigError::Invalid { field: "log_rotation.max_files".to_string(), reason: "no rotated files would be kept".to_string() });
+            self.log_rotation.max_files = LogRotationConfig::default().max_files;
+        }
+        logon_logoff.watcher.validate(Watcher::LogonLogoff.name(), &defaults.watcher, &mut errors);
+        self.presence.watcher.validate(Watcher::Presence.name(), &PresenceConfig::default().watcher, &mut errors);
+        self.failed_logon.watcher.validate(Watcher::FailedLogon.name(), &FailedLogonConfig::default().watcher, &mut errors);
+        self.audit_integrity.watcher.validate(Watcher::AuditIntegrity.name(), &AuditIntegrityConfig::default().watcher, &mut errors);
+        if self.audit_policy.check_interval_secs < 60 {
+            errors.push(ConfigError::Invalid { field: "audit_policy.check_interval_secs".to_string(), reason: format!("{}s is shorter than a minute", self.audit_policy.check_interval_secs) });
+            self.audit_policy.check_interval_secs = AuditPolicyConfig::default().check_interval_secs;
+        }
+        if self.heartbeat.interval_secs < 10 {
+            errors.push(ConfigError::Invalid { field: "heartbeat.interval_secs".to_string(), reason: format!("{}s is shorter than 10 seconds", self.heartbeat.interval_secs) });
+            self.heartbeat.interval_secs = HeartbeatConfig::default().interval_secs;
+        }
+        if self.event_queue.capacity == 0 {
+            errors.push(ConfigError::Invalid { field: "event_queue.capacity".to_string(), reason: "no events could be queued".to_string() });
+            self.event_queue.capacity = EventQueueConfig::default().capacity;
+        }
+        if self.failed_logon.logon_types.is_empty() {
+            errors.push(ConfigError::Invalid { field: "failed_logon.logon_types".to_string(), reason: "no logon types".to_string() });
+            self.failed_logon.logon_types = FailedLogonConfig::default().logon_types;
+        }
+        if logon_logoff.xpath.is_none() && logon_logoff.logon_types.is_empty() {
+            errors.push(ConfigError::Invalid { field: "logon_logoff.logon_types".to_string(), reason: "no logon types".to_string() });
+            logon_logoff.logon_types = defaults.logon_types;
+        }
+        if logon_logoff.xpath.as_ref().is_some_and(|xpath| xpath.trim().is_empty()) {
+            errors.push(ConfigError::Invalid { field: "logon_logoff.xpath".to_string(), reason: "query is empty".to_string() });
+            logon_logoff.xpath = None;
+        }
+        if let Some(sid) = logon_logoff.ignored_sid_prefixes.iter().find(|sid| !sid.starts_with("S-1-")) {
+            errors.push(ConfigError::Invalid { field: "logon_logoff.ignored_sid_prefixes".to_string(), reason: format!("{} is not a SID", sid) });
+            logon_logoff.ignored_sid_prefixes = defaults.ignored_sid_prefixes;
+        }
+
+        if let Some(webhook) = &mut self.webhook {
+            let webhook_defaults = WebhookConfig::default();
+            if !is_allowed_webhook_url(&webhook.url) {
+                errors.push(ConfigError::Invalid { field: "webhook.url".to_string(), reason: format!("'{}' is not an https:// URL, the webhook is disabled", webhook.url) });
+                self.webhook = None;
+            } else {
+                if webhook.batch_size == 0 {
+                    errors.push(ConfigError::Invalid { field: "webhook.batch_size".to_string(), reason: "batch size is 0".to_string() });
+                    webhook.batch_size = webhook_defaults.batch_size;
+                }
+                if webhook.flush_interval_secs == 0 {
+                    errors.push(ConfigError::Invalid { field: "webhook.flush_interval_secs".to_string(), reason: "flush interval is 0".to_string() });
+                    webhook.flush_interval_secs = webhook_defaults.flush_interval_secs;
+                }
+                if webhook.timeout_secs == 0 {
+                    errors.push(ConfigError::Invalid { field: "webhook.timeout_secs".to_string(), reason: "timeout is 0".to_string() });
+                    webhook.timeout_secs = webhook_defaults.timeout_secs;
+                }
+                if webhook.initial_backoff_ms == 0 {
+                    errors.push(ConfigError::Invalid { field: "webhook.initial_backoff_ms".to_string(), reason: "backoff is 0".to_string() });
+                    webhook.initial_backoff_ms = webhook_defaults.initial_backoff_ms;
+                }
+                if webhook.max_backoff() < webhook.initial_backoff() {
+                    errors.push(ConfigError::Invalid { field: "webhook.max_backoff_secs".to_string(), reason: format!("{}s is shorter than initial_backoff_ms", webhook.max_backoff_secs) });
+                    webhook.max_backoff_secs = webhook_defaults.max_backoff_secs.max(webhook.initial_backoff().as_secs().saturating_add(1));
+                }
+            }
+        }
+        errors
+    }
+
+    pub fn log_dir(&self) -> PathBuf {
+        self.log_dir.clone().unwrap_or_else(default_log_dir)
+    }
+
+    /// The enabled watchers with their settings and event filter, the logon/logoff watcher
+    /// always runs.
+    pub fn watchers(&self) -> Vec<(Watcher, &WatcherConfig, String)> {
+        let mut watchers = vec![(Watcher::LogonLogoff, &self.logon_logoff.watcher, self.logon_logoff.xpath())];
+        if self.presence.enabled {
+            watchers.push((Watcher::Presence, &self.presence.watcher, self.presence.xpath()));
+        }
+        if self.failed_logon.enabled {
+            watchers.push((Watcher::FailedLogon, &self.failed_logon.watcher, self.failed_logon.xpath()));
+        }
+        if self.audit_integrity.enabled {
+            watchers.push((Watcher::AuditIntegrity, &self.audit_integrity.watcher, self.audit_integrity.xpath()));
+        }
+        watchers
+    }
+}
+
+/// The parent of the directory holding `cua.exe`.
+pub fn default_log_dir() -> PathBuf {
+    let mut path = std::env::current_exe().unwrap_or("C:\\ProgramData\\cua\\cua.exe".into());
+    _ = path.pop();
+    _ = path.pop();
+    path
+}
+
+/// Records can hold usernames so only HTTPS is allowed, apart from plain HTTP to this machine.
+fn is_allowed_webhook_url(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+    url.starts_with("https://") || ["http://localhost", "http://127.0.0.1", "http://[::1]"].iter().any(|local| {
+        url.strip_prefix(local).is_some_and(|rest| rest.is_empty() || rest.starts_with([':', '/']))
+    })
+}
+
+/// Parses the config along with the keys it ignored, so one unknown key doesn't throw away
+/// every other setting.
+fn parse(contents: &str) -> Result<(Config, Vec<String>), toml::de::Error> {
+    let config = toml::from_str::<Config>(contents)?;
+    let mut unknown = Vec::new();
+    // Keys that are read come back when the config is written out again, whichever section
+    // (or flattened `WatcherConfig`) they belong to.
+    if let Ok(toml::Value::Table(known)) = toml::Value::try_from(&config) {
+        unknown_keys("", &toml::from_str(contents)?, &known, &mut unknown);
+    }
+    Ok((config, unknown))
+}
+
+/// Adds the paths of the keys in `table` that aren't in `known`.
+fn unknown_keys(prefix: &str, table: &toml::Table, known: &toml::Table, unknown: &mut Vec<String>) {
+    for (key, value) in table {
+        let path = format!("{}{}", prefix, key);
+        match (value, known.get(key)) {
+            (_, None) => unknown.push(path),
+            (toml::Value::Table(table), Some(toml::Value::Table(known))) => unknown_keys(&format!("{}.", path), table, known, unknown),
+            (toml::Value::Array(values), Some(toml::Value::Array(known))) => {
+                for (index, (value, known)) in values.iter().zip(known).enumerate() {
+                    if let (toml::Value::Table(table), toml::Value::Table(known)) = (value, known) {
+                        unknown_keys(&format!("{}.{}.", path, index), table, known, unknown);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+fn search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Ok(exe) = std::env::current_exe()
+        && let Some(exe_dir) = exe.parent() {
+        paths.push(exe_dir.join(CONFIG_FILE_NAME));
+    }
+    let program_data = std::env::var_os("ProgramData").unwrap_or("C:\\ProgramData".into());
+    paths.push(PathBuf::from(program_data).join("cua").join(CONFIG_FILE_NAME));
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_str(contents: &str) -> LoadedConfig {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        std::fs::write(&path, contents).unwrap();
+        Config::load_from(&path)
+    }
+
+    #[test]
+    fn default_query_matches_original() {
+        assert_eq!(
+            Config::default().logon_logoff.xpath(),
+            "Event[((System[(EventID='4624')] and EventData[Data[@Name='LogonType']='2' or Data[@Name='LogonType']='7' or Data[@Name='LogonType']='10' or Data[@Name='LogonType']='11']) or System[(EventID='4647')])]"
+        );
+    }
+
+    #[test]
+    fn empty_file_uses_defaults() {
+        let loaded = load_str("");
+        assert!(loaded.errors.is_empty());
+        assert_eq!(loaded.config, Config::default());
+    }
+
+    #[test]
+    fn settings_override_defaults() {
+        let loaded = load_str("log_dir = '/var/log/cua'\n[logon_logoff]\nlogon_types = [2, 10]\nignored_sid_prefixes = ['S-1-5-90']\ndebounce_ms = 0\n");
+        assert!(loaded.errors.is_empty(), "{:?}", loaded.errors);
+        let logon_logoff = &loaded.config.logon_logoff;
+        assert_eq!(loaded.config.log_dir(), PathBuf::from("/var/log/cua"));
+        assert_eq!(logon_logoff.xpath(), logon_logoff_xpath(&[2, 10]));
+        assert_eq!(logon_logoff.ignored_sid_prefixes, vec!["S-1-5-90".to_string()]);
+        assert_eq!(logon_logoff.watcher.debounce(), None);
+        assert_eq!(logon_logoff.watcher.channel, "Security");
+    }
+
+    #[test]
+    fn debounce_strategy_is_read() {
+        let loaded = load_str("[logon_logoff]
+debounce_strategy = 'logon_id'
+[presence]
+debounce_strategy = 'trailing'
+");
+        assert!(loaded.errors.is_empty(), "{:?}", loaded.errors);
+        assert_eq!(loaded.config.logon_logoff.watcher.debounce_strategy, DebounceStrategy::LogonId);
+        assert_eq!(loaded.config.presence.watcher.debounce_strategy, DebounceStrategy::Trailing);
+        assert_eq!(Config::default().logon_logoff.watcher.debounce_strategy, DebounceStrategy::Leading);
+
+        let loaded = load_str("[logon_logoff]
+debounce_strategy = 'newest'
+");
+        assert_eq!(loaded.errors.len(), 1);
+        assert_eq!(loaded.config, Config::default());
+    }
+
+    #[test]
+    fn invalid_settings_fall_back_to_defaults() {
+        let loaded = load_str("[log_rotation]\nmax_files = 0\n[logon_logoff]\nchannel = ''\nignored_sid_prefixes = ['DWM']\ndebounce_ms = 120000\n[presence]\ndebounce_ms = 60001\n[event_queue]\ncapacity = 0\n");
+        assert_eq!(loaded.errors.len(), 6);
+        assert_eq!(loaded.config, Config::default());
+    }
+
+    #[test]
+    fn selects_add_channels_to_the_query() {
+        let loaded = load_str("[[logon_logoff.select]]
+channel = 'ForwardedEvents'
+[[presence.select]]
+channel = 'Microsoft-Windows-TerminalServices-LocalSessionManager/Operational'
+xpath = \"Event[System[(EventID='24' or EventID='25')]]\"
+[[failed_logon.select]]
+channel = ''
+");
+        assert_eq!(loaded.errors.len(), 1, "{:?}", loaded.errors);
+        let config = loaded.config;
+        let logon_logoff = config.logon_logoff.xpath();
+        assert_eq!(
+            config.logon_logoff.watcher.query(&logon_logoff),
+            QueryList::channel("Security", &logon_logoff).select("ForwardedEvents", &logon_logoff)
+        );
+        let presence = config.presence.watcher.query(&config.presence.xpath());
+        assert_eq!(presence.selects.len(), 2);
+        assert_eq!(presence.selects[1].xpath, "Event[System[(EventID='24' or EventID='25')]]");
+        let failed_logon = config.failed_logon.xpath();
+        assert_eq!(config.failed_logon.watcher.query(&failed_logon), QueryList::channel("Security", &failed_logon));
+    }
+
+    #[test]
+    fn every_watcher_has_the_shared_settings() {
+        let loaded = load_str("[failed_logon]
+channel = 'ForwardedEvents'
+debounce_ms = 5000
+debounce_strategy = 'sid'
+look_back_secs = 60
+[audit_integrity]
+channel = ''
+[presence]
+enabled = false
+");
+        assert_eq!(loaded.errors.len(), 1, "{:?}", loaded.errors);
+        let config = loaded.config;
+        let watchers = config.watchers();
+        assert_eq!(watchers.iter().map(|(watcher, ..)| *watcher).collect::<Vec<Watcher>>(), vec![Watcher::LogonLogoff, Watcher::FailedLogon, Watcher::AuditIntegrity]);
+        let (_, failed_logon, xpath) = &watchers[1];
+        assert_eq!(failed_logon.query(xpath), QueryList::channel("ForwardedEvents", xpath));
+        assert_eq!(failed_logon.debounce(), Some(Duration::from_secs(5)));
+        assert_eq!(failed_logon.debounce_strategy, DebounceStrategy::Sid);
+        assert_eq!(failed_logon.look_back(), Duration::from_secs(60));
+        assert_eq!(watchers[2].1.channel, "Security");
+    }
+
+    #[test]
+    fn webhook_is_off_by_default_and_needs_https() {
+        assert_eq!(Config::default().webhook, None);
+
+        let loaded = load_str("[webhook]\nurl = 'https://siem.example.com/ingest'\nheaders = { Authorization = 'Bearer abc' }\n");
+        assert!(loaded.errors.is_empty(), "{:?}", loaded.errors);
+        let webhook = loaded.config.webhook.unwrap();
+        assert_eq!(webhook.headers["Authorization"], "Bearer abc");
+        assert_eq!(webhook.batch_size, 50);
+
+        let loaded = load_str("[webhook]\nurl = 'http://siem.example.com/ingest'\n");
+        assert_eq!(loaded.errors.len(), 1);
+        assert_eq!(loaded.config.webhook, None);
+
+        let loaded = load_str("[webhook]\nurl = 'https://siem.example.com/ingest'\nflush_interval_secs = 0\ntimeout_secs = 0\ninitial_backoff_ms = 0\nmax_backoff_secs = 0\n");
+        assert_eq!(loaded.errors.len(), 4);
+        let webhook = loaded.config.webhook.unwrap();
+        assert_eq!(webhook, WebhookConfig { url: webhook.url.clone(), ..WebhookConfig::default() });
+
+        assert!(is_allowed_webhook_url("http://127.0.0.1:8080/ingest"));
+        assert!(!is_allowed_webhook_url("http://127.0.0.1.example.com/ingest"));
+    }
+
+    #[test]
+    fn unknown_settings_are_ignored() {
+        let loaded = load_str("log_dir = '/var/log/cua'\n[logon_logoff]\ndebounce = 100\nlogon_types = [2]\n[[logon_logoff.select]]\nchannel = 'ForwardedEvents'\npath = 'x'\n[webhook]\nurl = 'https://cua.example.com/ingest'\n[newer_section]\nenabled = true\n");
+        let unknown = loaded.errors.iter().map(|err| err.to_string()).collect::<Vec<String>>();
+        assert_eq!(unknown, vec![
+            "Ignoring unknown setting logon_logoff.debounce",
+            "Ignoring unknown setting logon_logoff.select.0.path",
+            "Ignoring unknown setting newer_section",
+        ]);
+        assert_eq!(loaded.config.log_dir, Some(PathBuf::from("/var/log/cua")));
+        assert_eq!(loaded.config.logon_logoff.logon_types, vec![2]);
+        assert_eq!(loaded.config.logon_logoff.watcher.select.len(), 1);
+        assert!(loaded.config.webhook.is_some());
+    }
+
+    #[test]
+    fn unparseable_file_uses_defaults() {
+        let loaded = load_str("[logon_logoff\ndebounce_ms = 100\n");
+        assert!(matches!(loaded.errors.as_slice(), [ConfigError::Parse(..)]));
+        assert_eq!(loaded.config, Config::default());
+
+        let missing = Config::load_from(Path::new("/nonexistent/cua.toml"));
+        assert!(matches!(missing.errors.as_slice(), [ConfigError::Read(..)]));
+    }
+}
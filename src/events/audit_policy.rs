@@ -0,0 +1,86 @@
+//! The audit policy subcategories the watchers' events depend on. Windows only writes 4624
+//! and the rest when their subcategory is audited, so with it turned off cua sees nothing
+//! rather than an error.
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use super::queue::Watcher;
+
+/// Subcategory GUIDs as `AuditQuerySystemPolicy` takes them, and their names in `auditpol`.
+pub const SUBCATEGORIES: [(&str, &str); 12] = [
+    ("0cce9215-69ae-11d9-bed3-505054503030", "Logon"),
+    ("0cce9216-69ae-11d9-bed3-505054503030", "Logoff"),
+    ("0cce9217-69ae-11d9-bed3-505054503030", "Account Lockout"),
+    ("0cce9218-69ae-11d9-bed3-505054503030", "IPsec Main Mode"),
+    ("0cce9219-69ae-11d9-bed3-505054503030", "IPsec Quick Mode"),
+    ("0cce921a-69ae-11d9-bed3-505054503030", "IPsec Extended Mode"),
+    ("0cce921b-69ae-11d9-bed3-505054503030", "Special Logon"),
+    ("0cce921c-69ae-11d9-bed3-505054503030", "Other Logon/Logoff Events"),
+    ("0cce9243-69ae-11d9-bed3-505054503030", "Network Policy Server"),
+    ("0cce9247-69ae-11d9-bed3-505054503030", "User / Device Claims"),
+    ("0cce9249-69ae-11d9-bed3-505054503030", "Group Membership"),
+    ("0cce922f-69ae-11d9-bed3-505054503030", "Audit Policy Change"),
+];
+
+const LOGON: &str = "0cce9215-69ae-11d9-bed3-505054503030";
+const LOGOFF: &str = "0cce9216-69ae-11d9-bed3-505054503030";
+const OTHER_LOGON_LOGOFF_EVENTS: &str = "0cce921c-69ae-11d9-bed3-505054503030";
+const AUDIT_POLICY_CHANGE: &str = "0cce922f-69ae-11d9-bed3-505054503030";
+
+/// The subcategory's name, the GUID can be in either case and in braces as 4719 logs it.
+pub fn subcategory_name(guid: &str) -> Option<&'static str> {
+    let guid = guid.trim().trim_start_matches('{').trim_end_matches('}');
+    SUBCATEGORIES
+        .iter()
+        .find(|(subcategory_guid, _)| subcategory_guid.eq_ignore_ascii_case(guid))
+        .map(|(_, name)| *name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// An audit setting a watcher's events need.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Requirement {
+    pub subcategory_guid: &'static str,
+    pub outcome: AuditOutcome,
+    pub event_ids: &'static [u32],
+    pub watcher: Watcher,
+}
+
+pub const REQUIREMENTS: [Requirement; 5] = [
+    Requirement { subcategory_guid: LOGON, outcome: AuditOutcome::Success, event_ids: &[4624], watcher: Watcher::LogonLogoff },
+    Requirement { subcategory_guid: LOGOFF, outcome: AuditOutcome::Success, event_ids: &[4647], watcher: Watcher::LogonLogoff },
+    Requirement { subcategory_guid: LOGON, outcome: AuditOutcome::Failure, event_ids: &[4625], watcher: Watcher::FailedLogon },
+    Requirement { subcategory_guid: OTHER_LOGON_LOGOFF_EVENTS, outcome: AuditOutcome::Success, event_ids: &[4800, 4801, 4802, 4803], watcher: Watcher::Presence },
+    Requirement { subcategory_guid: AUDIT_POLICY_CHANGE, outcome: AuditOutcome::Success, event_ids: &[4719, 4907], watcher: Watcher::AuditIntegrity },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_found_by_guid() {
+        assert_eq!(subcategory_name("0cce9215-69ae-11d9-bed3-505054503030"), Some("Logon"));
+        assert_eq!(subcategory_name("{0CCE921C-69AE-11D9-BED3-505054503030}"), Some("Other Logon/Logoff Events"));
+        assert_eq!(subcategory_name("0cce9210-69ae-11d9-bed3-505054503030"), None);
+        assert_eq!(subcategory_name(""), None);
+    }
+
+    #[test]
+    fn table_is_consistent() {
+        for (index, (guid, name)) in SUBCATEGORIES.iter().enumerate() {
+            assert_eq!(guid.len(), 36, "{}", name);
+            assert_eq!(*guid, guid.to_ascii_lowercase(), "{}", name);
+            assert!(SUBCATEGORIES[index + 1..].iter().all(|(other, _)| other != guid), "{} is listed twice", name);
+        }
+        for requirement in REQUIREMENTS {
+            assert!(subcategory_name(requirement.subcategory_guid).is_some(), "{}", requirement.subcategory_guid);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Matches logoffs (4634, 4647) to the logon (4624) that opened the session by
+//! `TargetLogonId`. Logons stay open until their logoff, the oldest are dropped once there
+//! are more than `max_open` so a device that never logs anyone off can't grow it forever.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::EventIdType;
+use super::logon::LogonEvent;
+
+pub const DEFAULT_MAX_OPEN_LOGONS: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenLogon {
+    pub logon_id: u64,
+    pub linked_logon_id: Option<u64>,
+    pub user_sid: String,
+    pub logon_type: Option<u32>,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Correlation {
+    /// A logon, kept until its logoff.
+    Opened,
+    /// A logoff and the logon it ends.
+    Matched(OpenLogon),
+    /// A logoff for a logon that was never seen or has been dropped.
+    Unmatched,
+    /// Neither a logon nor a logoff, or without a logon ID.
+    Ignored,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogonTracker {
+    #[serde(skip, default = "default_max_open")]
+    max_open: usize,
+    open: HashMap<u64, OpenLogon>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl LogonTracker {
+    pub fn new(max_open: usize) -> Self {
+        Self { max_open, open: HashMap::new(), changed: false }
+    }
+
+    #[cfg(test)]
+    pub fn open_count(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Whether a logon was opened or closed since the last call.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn observe(&mut self, event_type: EventIdType, time: DateTime<Utc>, logon: &LogonEvent) -> Correlation {
+        let Some(logon_id) = logon.target_logon_id else {
+            return Correlation::Ignored;
+        };
+        match event_type {
+            EventIdType::Logon => {
+                self.open.insert(logon_id, OpenLogon {
+                    logon_id,
+                    linked_logon_id: logon.target_linked_logon_id,
+                    user_sid: logon.target_user_sid.clone(),
+                    logon_type: logon.logon_type,
+                    time,
+                });
+                self.evict();
+                self.changed = true;
+                Correlation::Opened
+            },
+            EventIdType::Logoff | EventIdType::LogoffInteractive => match self.close(logon_id) {
+                Some(open) => {
+                    self.changed = true;
+                    Correlation::Matched(open)
+                },
+                None => Correlation::Unmatched
+            },
+            _ => Correlation::Ignored
+        }
+    }
+
+    /// Removes the logon and its linked half of a split admin token, returning the earlier of
+    /// the two. A logoff can carry the ID of either half.
+    fn close(&mut self, logon_id: u64) -> Option<OpenLogon> {
+        let logon_id = match self.open.contains_key(&logon_id) {
+            true => logon_id,
+            false => self.open.values().find(|open| open.linked_logon_id == Some(logon_id))?.logon_id
+        };
+        let open = self.open.remove(&logon_id)?;
+        let linked = open.linked_logon_id.and_then(|linked_logon_id| self.open.remove(&linked_logon_id));
+        match linked {
+            Some(linked) if linked.time < open.time => Some(linked),
+            _ => Some(open)
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.open.len() > self.max_open {
+            let Some(oldest) = self.open.values().min_by_key(|open| open.time).map(|open| open.logon_id) else {
+                return;
+            };
+            self.open.remove(&oldest);
+        }
+    }
+}
+
+impl Default for LogonTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_OPEN_LOGONS)
+    }
+}
+
+fn default_max_open() -> usize {
+    DEFAULT_MAX_OPEN_LOGONS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Event;
+    use crate::test_support::event_at;
+    use chrono::TimeDelta;
+
+    const USER_SID: &str = "S-1-12-1-2991438786-1147252871-734652841-3570430303";
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        "2026-02-27T05:00:00Z".parse::<DateTime<Utc>>().unwrap() + TimeDelta::minutes(minutes)
+    }
+
+    fn logon(minutes: i64, logon_id: &str, linked_logon_id: &str) -> Event {
+        event_at(at(minutes), 4624, &[
+            ("TargetUserSid", USER_SID),
+            ("TargetLogonId", logon_id),
+            ("LogonType", "2"),
+            ("TargetLinkedLogonId", linked_logon_id),
+        ])
+    }
+
+    fn logoff(minutes: i64, logon_id: &str) -> Event {
+        event_at(at(minutes), 4647, &[("TargetUserSid", USER_SID), ("TargetLogonId", logon_id)])
+    }
+
+    fn observe(tracker: &mut LogonTracker, event: Event) -> Correlation {
+        let logon = LogonEvent::from_event_data(event.event_data.as_ref().unwrap()).unwrap();
+        tracker.observe(event.system.get_event_id_type(), event.time_created(), &logon)
+    }
+
+    #[test]
+    fn logoff_matches_its_logon() {
+        let mut tracker = LogonTracker::default();
+        assert_eq!(observe(&mut tracker, logon(0, "0x4a0b2c", "0x0")), Correlation::Opened);
+        assert_eq!(observe(&mut tracker, logon(5, "0x51f3a0", "0x0")), Correlation::Opened);
+
+        let Correlation::Matched(open) = observe(&mut tracker, logoff(30, "0x4a0b2c")) else {
+            panic!("logoff was not matched");
+        };
+        assert_eq!(open.time, at(0));
+        assert_eq!(open.logon_type, Some(2));
+        assert_eq!(tracker.open_count(), 1);
+        assert_eq!(observe(&mut tracker, logoff(31, "0x4a0b2c")), Correlation::Unmatched);
+    }
+
+    #[test]
+    fn split_token_is_closed_by_either_half() {
+        let mut tracker = LogonTracker::default();
+        observe(&mut tracker, logon(0, "0x4a0b2c", "0x4a0b5e"));
+        observe(&mut tracker, logon(0, "0x4a0b5e", "0x4a0b2c"));
+        observe(&mut tracker, logon(1, "0x600000", "0x0"));
+
+        assert!(matches!(observe(&mut tracker, logoff(30, "0x4a0b5e")), Correlation::Matched(_)));
+        assert_eq!(tracker.open_count(), 1);
+
+        // Only the elevated half was seen, the logoff names the filtered one
+        observe(&mut tracker, logon(40, "0x700002", "0x700001"));
+        assert!(matches!(observe(&mut tracker, logoff(50, "0x700001")), Correlation::Matched(_)));
+        assert_eq!(tracker.open_count(), 1);
+    }
+
+    #[test]
+    fn oldest_logons_are_dropped() {
+        let mut tracker = LogonTracker::new(2);
+        observe(&mut tracker, logon(0, "0x1", "0x0"));
+        observe(&mut tracker, logon(1, "0x2", "0x0"));
+        observe(&mut tracker, logon(2, "0x3", "0x0"));
+
+        assert_eq!(tracker.open_count(), 2);
+        assert!(tracker.take_changed());
+        assert_eq!(observe(&mut tracker, logoff(10, "0x1")), Correlation::Unmatched);
+        assert!(!tracker.take_changed());
+        assert!(matches!(observe(&mut tracker, logoff(10, "0x2")), Correlation::Matched(_)));
+    }
+
+    #[test]
+    fn events_without_logon_id_are_ignored() {
+        let mut tracker = LogonTracker::default();
+        let no_logon_id = event_at(at(0), 4647, &[("TargetUserSid", USER_SID)]);
+        assert_eq!(observe(&mut tracker, no_logon_id), Correlation::Ignored);
+        assert_eq!(tracker.open_count(), 0);
+        assert!(!tracker.take_changed());
+    }
+}
@@ -0,0 +1,363 @@
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::Event;
+use crate::platform::EventCallback;
+
+/// How events arriving within the debounce window of each other are coalesced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DebounceStrategy {
+    /// The first event is delivered, the rest are dropped.
+    #[default]
+    Leading,
+    /// The last event is delivered once no other event has arrived for the window.
+    Trailing,
+    /// Trailing, but separately for each `TargetUserSid` so one user's events never hide
+    /// another's.
+    Sid,
+    /// Trailing, separately for each `TargetLogonId`.
+    LogonId,
+}
+
+impl DebounceStrategy {
+    fn key(&self, event: &Event) -> Option<String> {
+        let field = match self {
+            DebounceStrategy::Leading | DebounceStrategy::Trailing => return None,
+            DebounceStrategy::Sid => "TargetUserSid",
+            DebounceStrategy::LogonId => "TargetLogonId",
+        };
+        event.event_data.as_ref().and_then(|event_data| event_data.get_value(field))
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    key: Option<String>,
+    last_at: DateTime<Utc>,
+    event: Event,
+}
+
+/// Coalesces events raised within `window` of each other. Time is measured between the
+/// times the events were raised so a backlog delivered in one burst is debounced the same as
+/// live events. Trailing strategies hold events back, `flush_due` releases them once the
+/// window has passed.
+#[derive(Debug)]
+pub struct Debouncer {
+    window: Option<Duration>,
+    strategy: DebounceStrategy,
+    last_delivered: Option<DateTime<Utc>>,
+    pending: Vec<Pending>,
+    /// The latest event time seen and when that event arrived, to keep time going between
+    /// events without switching to the wall clock.
+    latest: Option<(DateTime<Utc>, Instant)>,
+}
+
+impl Debouncer {
+    pub fn new(window: Option<Duration>, strategy: DebounceStrategy) -> Self {
+        Self { window, strategy, last_delivered: None, pending: Vec::new(), latest: None }
+    }
+
+    /// Whether `flush_due` needs calling for held back events to be delivered.
+    pub fn holds_events(&self) -> bool {
+        self.window.is_some() && self.strategy != DebounceStrategy::Leading
+    }
+
+    /// Takes an event and returns the events to deliver now, in the order they were raised.
+    pub fn push(&mut self, event: Event) -> Vec<Event> {
+        self.push_arrived(event, Instant::now())
+    }
+
+    fn push_arrived(&mut self, event: Event, arrived: Instant) -> Vec<Event> {
+        let Some(window) = self.window else {
+            // No debouncing, deliver immediately
+            return vec![event];
+        };
+        let at = event.time_created();
+        if self.latest.is_none_or(|(latest, _)| at >= latest) {
+            self.latest = Some((at, arrived));
+        }
+
+        if self.strategy == DebounceStrategy::Leading {
+            let should_deliver = match self.last_delivered {
+                None => true, // first event
+                Some(last) => elapsed(last, at) >= window,
+            };
+            if !should_deliver {
+                return Vec::new();
+            }
+            self.last_delivered = Some(at);
+            return vec![event];
+        }
+
+        let due = self.flush_due(at);
+        let key = self.strategy.key(&event);
+        match self.pending.iter_mut().find(|pending| pending.key == key) {
+            Some(pending) => {
+                pending.last_at = at;
+                pending.event = event;
+            },
+            None => self.pending.push(Pending { key, last_at: at, event })
+        }
+        due
+    }
+
+    /// Releases the held back events nothing has arrived after for the window as of `now`.
+    pub fn flush_due(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        let Some(window) = self.window else {
+            return Vec::new();
+        };
+        let (due, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|pending| elapsed(pending.last_at, now) >= window);
+        self.pending = pending;
+        into_events(due)
+    }
+
+    /// Releases the held back events whose window has passed, counting on from the latest
+    /// event's time by how long ago it arrived. Events with old times, e.g. a look-back
+    /// backlog, are held for their window the same as live ones.
+    pub fn flush_elapsed(&mut self) -> Vec<Event> {
+        self.flush_arrived(Instant::now())
+    }
+
+    fn flush_arrived(&mut self, now: Instant) -> Vec<Event> {
+        match self.latest {
+            Some((latest, arrived)) => self.flush_due(latest + now.saturating_duration_since(arrived)),
+            None => Vec::new()
+        }
+    }
+
+    /// Releases every held back event, e.g. at the end of a replay.
+    pub fn flush_all(&mut self) -> Vec<Event> {
+        into_events(std::mem::take(&mut self.pending))
+    }
+}
+
+/// The debouncer and the callback it delivers to, shared with the flush thread.
+struct Delivery {
+    debouncer: Debouncer,
+    callback: EventCallback,
+}
+
+impl Delivery {
+    fn deliver(&mut self, events: Vec<Event>) {
+        for event in events {
+            (self.callback)(event)
+        }
+    }
+}
+
+/// A watcher's callback behind its debouncer. When the strategy holds events back a thread
+/// delivers them once their window passes, and dropping this stops the thread and delivers
+/// whatever is still held so no event is lost when the watcher stops.
+pub struct DebouncedCallback {
+    delivery: Arc<Mutex<Delivery>>,
+    flush_thread: Option<(Sender<()>, JoinHandle<()>)>,
+}
+
+impl DebouncedCallback {
+    pub fn new(name: &str, window: Option<Duration>, strategy: DebounceStrategy, callback: EventCallback) -> io::Result<Self> {
+        let debouncer = Debouncer::new(window, strategy);
+        let holds_events = debouncer.holds_events();
+        let delivery = Arc::new(Mutex::new(Delivery { debouncer, callback }));
+        let flush_thread = match window {
+            Some(window) if holds_events => Some(spawn_flush_thread(name, delivery.clone(), window)?),
+            _ => None
+        };
+        Ok(Self { delivery, flush_thread })
+    }
+
+    pub fn push(&self, event: Event) {
+        let mut delivery = self.delivery.lock().unwrap();
+        let events = delivery.debouncer.push(event);
+        delivery.deliver(events);
+    }
+
+    /// Delivers every held back event now.
+    pub fn flush_all(&self) {
+        let mut delivery = self.delivery.lock().unwrap();
+        let events = delivery.debouncer.flush_all();
+        delivery.deliver(events);
+    }
+}
+
+impl Drop for DebouncedCallback {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread, once it has returned nothing else delivers
+        if let Some((stop, thread)) = self.flush_thread.take() {
+            drop(stop);
+            _ = thread.join();
+        }
+        let mut delivery = self.delivery.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let events = delivery.debouncer.flush_all();
+        delivery.deliver(events);
+    }
+}
+
+/// Delivers the events a trailing debounce is holding back once their window has passed,
+/// every `window` until the sender is dropped.
+fn spawn_flush_thread(name: &str, delivery: Arc<Mutex<Delivery>>, window: Duration) -> io::Result<(Sender<()>, JoinHandle<()>)> {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let thread = thread::Builder::new().name(format!("{}-debounce", name)).spawn(move || {
+        while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(window) {
+            let mut delivery = delivery.lock().unwrap();
+            let events = delivery.debouncer.flush_elapsed();
+            delivery.deliver(events);
+        }
+    })?;
+    Ok((stop, thread))
+}
+
+/// Zero when `to` is before `from`.
+fn elapsed(from: DateTime<Utc>, to: DateTime<Utc>) -> Duration {
+    (to - from).to_std().unwrap_or(Duration::ZERO)
+}
+
+fn into_events(mut pending: Vec<Pending>) -> Vec<Event> {
+    pending.sort_by_key(|pending| pending.event.time_created());
+    pending.into_iter().map(|pending| pending.event).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::event_at;
+
+    const ALICE: &str = "S-1-12-1-2991438786-1147252871-734652841-3570430303";
+    const BOB: &str = "S-1-5-21-406160441-2633804267-1261186540-1001";
+
+    fn start() -> DateTime<Utc> {
+        "2026-02-27T05:00:00Z".parse().unwrap()
+    }
+
+    fn event(ms: u64, event_id: u32, sid: &str, logon_id: &str) -> Event {
+        event_at(start() + Duration::from_millis(ms), event_id, &[("TargetUserSid", sid), ("TargetLogonId", logon_id)])
+    }
+
+    fn ids(events: &[Event]) -> Vec<u32> {
+        events.iter().map(|event| event.system.event_id).collect()
+    }
+
+    #[test]
+    fn no_window_always_delivers() {
+        let mut debouncer = Debouncer::new(None, DebounceStrategy::Trailing);
+        assert_eq!(debouncer.push(event(0, 4624, ALICE, "0x1")).len(), 1);
+        assert_eq!(debouncer.push(event(0, 4624, ALICE, "0x1")).len(), 1);
+        assert!(!debouncer.holds_events());
+    }
+
+    #[test]
+    fn leading_drops_events_inside_window() {
+        let mut debouncer = Debouncer::new(Some(Duration::from_millis(100)), DebounceStrategy::Leading);
+        assert_eq!(debouncer.push(event(0, 4624, ALICE, "0x1")).len(), 1);
+        assert_eq!(debouncer.push(event(50, 4624, BOB, "0x2")).len(), 0);
+        assert_eq!(debouncer.push(event(100, 4624, ALICE, "0x1")).len(), 1);
+        assert_eq!(debouncer.push(event(150, 4624, ALICE, "0x1")).len(), 0);
+        assert!(debouncer.flush_all().is_empty());
+    }
+
+    #[test]
+    fn trailing_delivers_last_event_once_quiet() {
+        let mut debouncer = Debouncer::new(Some(Duration::from_millis(100)), DebounceStrategy::Trailing);
+        assert!(debouncer.push(event(0, 4624, ALICE, "0x1")).is_empty());
+        assert!(debouncer.push(event(50, 4647, ALICE, "0x1")).is_empty());
+        // Each event restarts the window
+        assert!(debouncer.flush_due(start() + Duration::from_millis(120)).is_empty());
+        assert_eq!(ids(&debouncer.flush_due(start() + Duration::from_millis(150))), vec![4647]);
+
+        assert!(debouncer.push(event(300, 4624, BOB, "0x2")).is_empty());
+        assert_eq!(ids(&debouncer.push(event(450, 4624, ALICE, "0x3"))), vec![4624]);
+        assert_eq!(debouncer.flush_all().len(), 1);
+    }
+
+    #[test]
+    fn sid_keeps_each_users_last_event() {
+        let mut debouncer = Debouncer::new(Some(Duration::from_millis(100)), DebounceStrategy::Sid);
+        debouncer.push(event(0, 4624, ALICE, "0x1"));
+        debouncer.push(event(20, 4647, ALICE, "0x1"));
+        debouncer.push(event(40, 4624, BOB, "0x2"));
+
+        let delivered = debouncer.flush_due(start() + Duration::from_millis(200));
+        assert_eq!(ids(&delivered), vec![4647, 4624]);
+        assert_eq!(delivered[1].event_data.as_ref().unwrap().get_value("TargetUserSid").as_deref(), Some(BOB));
+    }
+
+    #[test]
+    fn logon_id_keeps_each_sessions_last_event() {
+        let mut debouncer = Debouncer::new(Some(Duration::from_millis(100)), DebounceStrategy::LogonId);
+        debouncer.push(event(0, 4624, ALICE, "0x1"));
+        debouncer.push(event(10, 4624, ALICE, "0x2"));
+        debouncer.push(event(20, 4624, ALICE, "0x1"));
+
+        assert_eq!(debouncer.flush_due(start() + Duration::from_millis(115)).len(), 1);
+        assert_eq!(debouncer.flush_due(start() + Duration::from_millis(120)).len(), 1);
+        assert!(debouncer.flush_all().is_empty());
+    }
+
+    #[test]
+    fn backlog_is_debounced_by_event_time() {
+        let mut debouncer = Debouncer::new(Some(Duration::from_millis(100)), DebounceStrategy::Sid);
+        let mut delivered = Vec::new();
+        for ms in [0, 30, 60, 500, 530] {
+            delivered.extend(debouncer.push(event(ms, 4624, ALICE, "0x1")));
+        }
+        delivered.extend(debouncer.flush_all());
+        assert_eq!(delivered.iter().map(|event| event.time_created()).collect::<Vec<_>>(), vec![start() + Duration::from_millis(60), start() + Duration::from_millis(530)]);
+    }
+
+    #[test]
+    fn old_events_are_held_for_their_window() {
+        let mut debouncer = Debouncer::new(Some(Duration::from_millis(100)), DebounceStrategy::Sid);
+        let arrived = Instant::now();
+        assert!(debouncer.push_arrived(event(0, 4624, ALICE, "0x1"), arrived).is_empty());
+        // Months old, but only 10ms have passed since it arrived
+        assert!(debouncer.flush_arrived(arrived + Duration::from_millis(10)).is_empty());
+        assert!(debouncer.push_arrived(event(30, 4647, ALICE, "0x1"), arrived + Duration::from_millis(10)).is_empty());
+        assert!(debouncer.flush_arrived(arrived + Duration::from_millis(50)).is_empty());
+
+        assert_eq!(ids(&debouncer.flush_arrived(arrived + Duration::from_millis(110))), vec![4647]);
+        assert!(debouncer.flush_all().is_empty());
+    }
+
+    fn collecting_callback() -> (EventCallback, Arc<Mutex<Vec<Event>>>) {
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let sink = delivered.clone();
+        (Box::new(move |event| sink.lock().unwrap().push(event)), delivered)
+    }
+
+    #[test]
+    fn held_events_are_delivered_on_drop() {
+        let (callback, delivered) = collecting_callback();
+        let debounced = DebouncedCallback::new("logon_logoff", Some(Duration::from_secs(3600)), DebounceStrategy::Sid, callback).unwrap();
+        debounced.push(event(0, 4624, ALICE, "0x1"));
+        debounced.push(event(10, 4624, BOB, "0x2"));
+        assert!(delivered.lock().unwrap().is_empty());
+
+        drop(debounced);
+        assert_eq!(ids(&delivered.lock().unwrap()), vec![4624, 4624]);
+    }
+
+    #[test]
+    fn flush_thread_delivers_once_window_passes() {
+        let (callback, delivered) = collecting_callback();
+        let debounced = DebouncedCallback::new("presence", Some(Duration::from_millis(10)), DebounceStrategy::Trailing, callback).unwrap();
+        debounced.push(event_at(Utc::now(), 4800, &[("TargetUserSid", ALICE)]));
+        for _ in 0..500 {
+            if !delivered.lock().unwrap().is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(ids(&delivered.lock().unwrap()), vec![4800]);
+
+        drop(debounced);
+        assert_eq!(delivered.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,111 @@
+//! Drops events that were already delivered, e.g. when a bookmark or look-back replays
+//! events seen before a restart. Record IDs only grow within a channel, so only the highest
+//! one seen in each channel is kept. Clearing a log starts its record IDs again from 1, a
+//! lower ID on an event raised after the highest one is taken as a clear rather than a
+//! duplicate.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::Event;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct HighWater {
+    record_id: u64,
+    time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Seen {
+    /// Newer than anything seen in its channel, or without a record ID to compare.
+    New,
+    /// At or below the channel's highest record ID.
+    Duplicate,
+    /// Below the highest record ID but raised later, the log was cleared in between.
+    LogCleared,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordIdTracker {
+    channels: HashMap<String, HighWater>,
+}
+
+impl RecordIdTracker {
+    pub fn observe(&mut self, event: &Event) -> Seen {
+        let (Some(record_id), Some(time)) = (event.system.event_record_id, event.system.time_created()) else {
+            return Seen::New;
+        };
+        self.observe_record(event.system.channel.as_deref().unwrap_or_default(), record_id, time)
+    }
+
+    /// The highest record ID seen in each channel and when it was raised.
+    pub fn highest(&self) -> impl Iterator<Item = (&str, u64, DateTime<Utc>)> {
+        self.channels.iter().map(|(channel, high_water)| (channel.as_str(), high_water.record_id, high_water.time))
+    }
+
+    fn observe_record(&mut self, channel: &str, record_id: u64, time: DateTime<Utc>) -> Seen {
+        let seen = match self.channels.get(channel) {
+            None => Seen::New,
+            Some(high_water) if record_id > high_water.record_id => Seen::New,
+            Some(high_water) if time > high_water.time => Seen::LogCleared,
+            Some(_) => return Seen::Duplicate
+        };
+        self.channels.insert(channel.to_string(), HighWater { record_id, time });
+        seen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        "2026-02-27T05:00:00Z".parse::<DateTime<Utc>>().unwrap() + TimeDelta::seconds(seconds)
+    }
+
+    fn observe_all(tracker: &mut RecordIdTracker, channel: &str, records: &[(u64, i64)]) -> Vec<Seen> {
+        records.iter().map(|(record_id, seconds)| tracker.observe_record(channel, *record_id, at(*seconds))).collect()
+    }
+
+    #[test]
+    fn replayed_record_ids_are_dropped() {
+        let mut tracker = RecordIdTracker::default();
+        assert_eq!(observe_all(&mut tracker, "Security", &[(100, 0), (101, 1), (102, 2)]), vec![Seen::New; 3]);
+        // Restart resumes from an older bookmark
+        assert_eq!(
+            observe_all(&mut tracker, "Security", &[(101, 1), (102, 2), (103, 3)]),
+            vec![Seen::Duplicate, Seen::Duplicate, Seen::New]
+        );
+        // Gaps are fine, other watchers' events take the IDs in between
+        assert_eq!(observe_all(&mut tracker, "Security", &[(250, 10)]), vec![Seen::New]);
+    }
+
+    #[test]
+    fn channels_are_tracked_separately() {
+        let mut tracker = RecordIdTracker::default();
+        assert_eq!(observe_all(&mut tracker, "Security", &[(48211, 0)]), vec![Seen::New]);
+        assert_eq!(observe_all(&mut tracker, "System", &[(312, 1), (312, 1)]), vec![Seen::New, Seen::Duplicate]);
+        assert_eq!(observe_all(&mut tracker, "Security", &[(48211, 0)]), vec![Seen::Duplicate]);
+    }
+
+    #[test]
+    fn log_clear_restarts_record_ids() {
+        let mut tracker = RecordIdTracker::default();
+        observe_all(&mut tracker, "Security", &[(9000, 0), (9001, 5)]);
+        assert_eq!(
+            observe_all(&mut tracker, "Security", &[(1, 60), (2, 61), (2, 61), (1, 60)]),
+            vec![Seen::LogCleared, Seen::New, Seen::Duplicate, Seen::Duplicate]
+        );
+    }
+
+    #[test]
+    fn events_without_record_id_are_new() {
+        let mut tracker = RecordIdTracker::default();
+        let event = crate::test_support::event(4624, &[]);
+        assert_eq!(tracker.observe(&event), Seen::New);
+        assert_eq!(tracker.observe(&event), Seen::New);
+    }
+}
@@ -0,0 +1,100 @@
+//! Events that mean the Security log can no longer be trusted to have every logon: the log
+//! being cleared, the event log service stopping, and auditing being changed.
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Event log service shutdown (1100), log cleared (1102), system audit policy changed (4719)
+/// and object auditing settings changed (4907).
+pub const INTEGRITY_EVENT_IDS: [u32; 4] = [1100, 1102, 4719, 4907];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum IntegrityEventType {
+    EventLogShutdown,
+    LogCleared,
+    AuditPolicyChanged,
+    ObjectAuditingChanged,
+}
+
+impl IntegrityEventType {
+    pub fn from_event_id(event_id: u32) -> Option<Self> {
+        match event_id {
+            1100 => Some(IntegrityEventType::EventLogShutdown),
+            1102 => Some(IntegrityEventType::LogCleared),
+            4719 => Some(IntegrityEventType::AuditPolicyChanged),
+            4907 => Some(IntegrityEventType::ObjectAuditingChanged),
+            _ => None
+        }
+    }
+}
+
+/// Text for the category, subcategory and change tokens 4719 logs, None for anything else.
+/// Only the Logon/Logoff subcategories are named, they are the ones cua depends on.
+pub fn audit_message_text(value: &str) -> Option<&'static str> {
+    let text = match value.trim() {
+        "%%8272" => "System",
+        "%%8273" => "Logon/Logoff",
+        "%%8274" => "Object Access",
+        "%%8275" => "Privilege Use",
+        "%%8276" => "Detailed Tracking",
+        "%%8277" => "Policy Change",
+        "%%8278" => "Account Management",
+        "%%8279" => "DS Access",
+        "%%8280" => "Account Logon",
+        "%%8448" => "Success removed",
+        "%%8449" => "Success added",
+        "%%8450" => "Failure removed",
+        "%%8451" => "Failure added",
+        "%%12544" => "Logon",
+        "%%12545" => "Logoff",
+        "%%12546" => "Account Lockout",
+        "%%12547" => "IPsec Main Mode",
+        "%%12548" => "Special Logon",
+        "%%12549" => "IPsec Quick Mode",
+        "%%12550" => "IPsec Extended Mode",
+        "%%12551" => "Other Logon/Logoff Events",
+        "%%12552" => "Network Policy Server",
+        "%%12553" => "User / Device Claims",
+        "%%12554" => "Group Membership",
+        _ => return None
+    };
+    Some(text)
+}
+
+/// The value with a token replaced by its text, anything else as is.
+pub fn decode_audit_message(value: String) -> String {
+    audit_message_text(&value).map(str::to_string).unwrap_or(value)
+}
+
+/// 4719's `AuditPolicyChanges`, e.g. `%%8448, %%8450`, as one entry for each change.
+pub fn audit_policy_changes(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|change| !change.is_empty())
+        .map(|change| decode_audit_message(change.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_ids_have_a_type() {
+        for event_id in INTEGRITY_EVENT_IDS {
+            assert!(IntegrityEventType::from_event_id(event_id).is_some(), "{}", event_id);
+        }
+        assert_eq!(IntegrityEventType::from_event_id(4624), None);
+    }
+
+    #[test]
+    fn audit_policy_tokens_are_decoded() {
+        assert_eq!(decode_audit_message("%%8273".to_string()), "Logon/Logoff");
+        assert_eq!(decode_audit_message("%%12551".to_string()), "Other Logon/Logoff Events");
+        assert_eq!(decode_audit_message("%%99999".to_string()), "%%99999");
+        assert_eq!(audit_policy_changes("%%8448, %%8450"), vec!["Success removed", "Failure removed"]);
+        assert_eq!(audit_policy_changes("%%8449"), vec!["Success added"]);
+        assert!(audit_policy_changes("").is_empty());
+    }
+}
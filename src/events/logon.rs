@@ -0,0 +1,177 @@
+//! Typed view of the `EventData` of logons (4624), and the subset of it logoffs (4634, 4647)
+//! carry. Fields Windows logs as `-`, or as zero for IDs and GUIDs, are None.
+
+use super::EventData;
+
+const EMPTY_GUID: &str = "{00000000-0000-0000-0000-000000000000}";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogonEvent {
+    pub target_user_sid: String,
+    pub target_user_name: Option<String>,
+    pub target_domain_name: Option<String>,
+    pub logon_type: Option<u32>,
+    pub target_logon_id: Option<u64>,
+    /// The other half of a split admin token, the elevated logon for the filtered one and back.
+    pub target_linked_logon_id: Option<u64>,
+    /// e.g. `User32`, `Advapi` or `CloudAP`.
+    pub logon_process_name: Option<String>,
+    /// e.g. `Negotiate`, `Kerberos`, `NTLM` or `CloudAP` for Windows Hello and Entra ID.
+    pub authentication_package_name: Option<String>,
+    pub elevated_token: Option<bool>,
+    pub virtual_account: Option<bool>,
+    pub ip_address: Option<String>,
+    pub ip_port: Option<u16>,
+    pub workstation_name: Option<String>,
+    pub logon_guid: Option<String>,
+}
+
+impl LogonEvent {
+    /// None without a `TargetUserSid`.
+    pub fn from_event_data(event_data: &EventData) -> Option<LogonEvent> {
+        Some(LogonEvent {
+            target_user_sid: event_data.get_present_value("TargetUserSid")?,
+            target_user_name: event_data.get_present_value("TargetUserName"),
+            target_domain_name: event_data.get_present_value("TargetDomainName"),
+            logon_type: event_data.get_present_value("LogonType").and_then(|logon_type| logon_type.parse().ok()),
+            target_logon_id: logon_id(event_data, "TargetLogonId"),
+            target_linked_logon_id: logon_id(event_data, "TargetLinkedLogonId"),
+            logon_process_name: event_data.get_present_value("LogonProcessName").map(|name| name.trim().to_string()),
+            authentication_package_name: event_data.get_present_value("AuthenticationPackageName"),
+            elevated_token: yes_no(event_data, "ElevatedToken"),
+            virtual_account: yes_no(event_data, "VirtualAccount"),
+            ip_address: event_data.get_present_value("IpAddress"),
+            ip_port: event_data.get_present_value("IpPort").and_then(|port| port.parse().ok()).filter(|&port| port != 0),
+            workstation_name: event_data.get_present_value("WorkstationName"),
+            logon_guid: event_data.get_present_value("LogonGuid").filter(|guid| guid != EMPTY_GUID),
+        })
+    }
+}
+
+/// Parses `0x4a0b2c` style logon IDs, None for `0x0`.
+pub fn parse_logon_id(value: &str) -> Option<u64> {
+    let hex = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X"))?;
+    u64::from_str_radix(hex, 16).ok().filter(|&logon_id| logon_id != 0)
+}
+
+fn logon_id(event_data: &EventData, name: &str) -> Option<u64> {
+    event_data.get_present_value(name).and_then(|value| parse_logon_id(&value))
+}
+
+fn yes_no(event_data: &EventData, name: &str) -> Option<bool> {
+    match message_text(&event_data.get_present_value(name)?)? {
+        "Yes" => Some(true),
+        "No" => Some(false),
+        _ => None
+    }
+}
+
+/// Text for the `%%1842` style tokens Windows logs in place of strings from the Security
+/// auditing message table, None for values that aren't a known token.
+pub fn message_text(value: &str) -> Option<&'static str> {
+    let text = match value.trim() {
+        "%%1832" => "Identification",
+        "%%1833" => "Impersonation",
+        "%%1840" => "Delegation",
+        "%%1841" => "Denied by Process Trust Label ACE",
+        "%%1842" => "Yes",
+        "%%1843" => "No",
+        "%%2304" => "An error occurred during logon",
+        "%%2305" => "The specified user account has expired",
+        "%%2306" => "The NetLogon component is not active",
+        "%%2307" => "Account locked out",
+        "%%2308" => "The user has not been granted the requested logon type at this machine",
+        "%%2309" => "The specified account's password has expired",
+        "%%2310" => "Account currently disabled",
+        "%%2311" => "Account logon time restriction violation",
+        "%%2312" => "User not allowed to logon at this computer",
+        "%%2313" => "Unknown user name or bad password",
+        _ => return None
+    };
+    Some(text)
+}
+
+/// The value with a message table token replaced by its text, anything else as is.
+pub fn decode_message(value: String) -> String {
+    message_text(&value).map(str::to_string).unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::event;
+
+    const USER_SID: &str = "S-1-12-1-2991438786-1147252871-734652841-3570430303";
+
+    #[test]
+    fn parses_every_logon_field() {
+        let logon = event(4624, &[
+            ("SubjectUserSid", "S-1-5-18"),
+            ("TargetUserSid", USER_SID),
+            ("TargetUserName", "JordanGomes"),
+            ("TargetDomainName", "AzureAD"),
+            ("TargetLogonId", "0x4a0b2c"),
+            ("LogonType", "10"),
+            ("LogonProcessName", "User32 "),
+            ("AuthenticationPackageName", "Negotiate"),
+            ("WorkstationName", "JORDANS-DESKTOP"),
+            ("LogonGuid", "{8f7c2a10-5d3e-4b1a-9c2f-0e6d4a7b1c3d}"),
+            ("IpAddress", "10.0.0.12"),
+            ("IpPort", "0"),
+            ("VirtualAccount", "%%1843"),
+            ("TargetLinkedLogonId", "0x4a0b5e"),
+            ("ElevatedToken", "%%1842"),
+        ]);
+
+        let parsed = LogonEvent::from_event_data(logon.event_data.as_ref().unwrap()).unwrap();
+        assert_eq!(parsed, LogonEvent {
+            target_user_sid: USER_SID.to_string(),
+            target_user_name: Some("JordanGomes".to_string()),
+            target_domain_name: Some("AzureAD".to_string()),
+            logon_type: Some(10),
+            target_logon_id: Some(0x4a0b2c),
+            target_linked_logon_id: Some(0x4a0b5e),
+            logon_process_name: Some("User32".to_string()),
+            authentication_package_name: Some("Negotiate".to_string()),
+            elevated_token: Some(true),
+            virtual_account: Some(false),
+            ip_address: Some("10.0.0.12".to_string()),
+            ip_port: None,
+            workstation_name: Some("JORDANS-DESKTOP".to_string()),
+            logon_guid: Some("{8f7c2a10-5d3e-4b1a-9c2f-0e6d4a7b1c3d}".to_string()),
+        });
+    }
+
+    #[test]
+    fn missing_and_empty_values_are_none() {
+        let logoff = event(4647, &[
+            ("TargetUserSid", USER_SID),
+            ("TargetUserName", "JordanGomes"),
+            ("TargetLogonId", "0x4a0b2c"),
+            ("TargetLinkedLogonId", "0x0"),
+            ("IpAddress", "-"),
+            ("LogonGuid", EMPTY_GUID),
+        ]);
+
+        let parsed = LogonEvent::from_event_data(logoff.event_data.as_ref().unwrap()).unwrap();
+        assert_eq!(parsed.target_logon_id, Some(0x4a0b2c));
+        assert_eq!(parsed.target_linked_logon_id, None);
+        assert_eq!(parsed.ip_address, None);
+        assert_eq!(parsed.logon_guid, None);
+        assert_eq!(parsed.elevated_token, None);
+        assert_eq!(parsed.logon_type, None);
+
+        let no_sid = event(4624, &[("TargetUserSid", "-")]);
+        assert_eq!(LogonEvent::from_event_data(no_sid.event_data.as_ref().unwrap()), None);
+    }
+
+    #[test]
+    fn message_tokens_are_decoded() {
+        assert_eq!(message_text("%%1842"), Some("Yes"));
+        assert_eq!(message_text("%%1833"), Some("Impersonation"));
+        assert_eq!(message_text("%%9999"), None);
+        assert_eq!(decode_message("%%2313".to_string()), "Unknown user name or bad password");
+        assert_eq!(decode_message("Negotiate".to_string()), "Negotiate");
+        assert_eq!(parse_logon_id("4a0b2c"), None);
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub mod audit_policy;
+pub mod correlation;
+pub mod debounce;
+pub mod dedupe;
+pub mod integrity;
+pub mod logon;
+pub mod ntstatus;
+pub mod query;
+pub mod queue;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Event {
+    #[serde(rename = "System")]
+    pub system: System,
+
+    #[serde(rename = "EventData")]
+    pub event_data: Option<EventData>,
+
+    #[serde(rename = "UserData")]
+    pub user_data: Option<UserData>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct System {
+    #[serde(rename = "Provider")]
+    pub provider: Option<Provider>,
+
+    #[serde(rename = "EventID")]
+    pub event_id: u32,
+
+    #[serde(rename = "TimeCreated")]
+    pub time_created: Option<TimeCreated>,
+
+    #[serde(rename = "EventRecordID")]
+    pub event_record_id: Option<u64>,
+
+    #[serde(rename = "Correlation")]
+    pub correlation: Option<Correlation>,
+
+    #[serde(rename = "Execution")]
+    pub execution: Option<Execution>,
+
+    #[serde(rename = "Channel")]
+    pub channel: Option<String>,
+
+    #[serde(rename = "Computer")]
+    pub computer: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Provider {
+    #[serde(rename = "@Name")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimeCreated {
+    #[serde(rename = "@SystemTime")]
+    pub system_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Correlation {
+    #[serde(rename = "@ActivityID")]
+    pub activity_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Execution {
+    #[serde(rename = "@ProcessID")]
+    pub process_id: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventData {
+    #[serde(rename = "Data")]
+    pub data: Vec<EventDataField>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum EventIdType {
+    Logon,
+    FailedLogon,
+    Logoff,
+    LogoffInteractive,
+    WorkstationLocked,
+    WorkstationUnlocked,
+    ScreensaverInvoked,
+    ScreensaverDismissed,
+    Unknown
+}
+
+impl std::fmt::Display for EventIdType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// e.g. `4624 #48211 from Microsoft-Windows-Security-Auditing in Security on jordans-desktop
+/// (pid 1008, activity {a1f3...})`, leaving out whatever the event doesn't have.
+impl std::fmt::Display for System {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.event_id)?;
+        if let Some(record_id) = self.event_record_id {
+            write!(f, " #{}", record_id)?;
+        }
+        if let Some(provider) = self.provider.as_ref().and_then(|provider| provider.name.as_ref()) {
+            write!(f, " from {}", provider)?;
+        }
+        if let Some(channel) = &self.channel {
+            write!(f, " in {}", channel)?;
+        }
+        if let Some(computer) = &self.computer {
+            write!(f, " on {}", computer)?;
+        }
+        let process_id = self.execution.as_ref().and_then(|execution| execution.process_id);
+        let activity_id = self.correlation.as_ref().and_then(|correlation| correlation.activity_id.as_ref());
+        match (process_id, activity_id) {
+            (Some(process_id), Some(activity_id)) => write!(f, " (pid {}, activity {})", process_id, activity_id),
+            (Some(process_id), None) => write!(f, " (pid {})", process_id),
+            (None, Some(activity_id)) => write!(f, " (activity {})", activity_id),
+            (None, None) => Ok(())
+        }
+    }
+}
+
+impl System {
+    pub fn time_created(&self) -> Option<DateTime<Utc>> {
+        self.time_created.as_ref().map(|time_created| time_created.system_time)
+    }
+
+    pub fn get_event_id_type(&self) -> EventIdType {
+        match self.event_id {
+            4624 => EventIdType::Logon,
+            4634 => EventIdType::Logoff,
+            4625 => EventIdType::FailedLogon,
+            4647 => EventIdType::LogoffInteractive,
+            4800 => EventIdType::WorkstationLocked,
+            4801 => EventIdType::WorkstationUnlocked,
+            4802 => EventIdType::ScreensaverInvoked,
+            4803 => EventIdType::ScreensaverDismissed,
+            _ => EventIdType::Unknown
+        }
+    }
+}
+
+impl EventIdType {
+    /// Lock, unlock and screensaver events, which say whether someone is at the keyboard.
+    pub fn is_presence(&self) -> bool {
+        matches!(self, EventIdType::WorkstationLocked | EventIdType::WorkstationUnlocked | EventIdType::ScreensaverInvoked | EventIdType::ScreensaverDismissed)
+    }
+}
+
+impl Event {
+    /// When Windows raised the event, or now for events without a `TimeCreated`.
+    pub fn time_created(&self) -> DateTime<Utc> {
+        self.system.time_created().unwrap_or_else(Utc::now)
+    }
+}
+
+impl Event {
+    /// A field from `EventData`, or from `UserData` for events that log their fields there.
+    /// None for empty and `-` values.
+    pub fn get_present_value(&self, field_name: &str) -> Option<String> {
+        self.event_data
+            .as_ref()
+            .and_then(|event_data| event_data.get_present_value(field_name))
+            .or_else(|| self.user_data.as_ref().and_then(|user_data| user_data.get_present_value(field_name)))
+    }
+}
+
+impl EventData {
+    pub fn get_value(&self, field_name: &str) -> Option<String> {
+        self.data
+            .iter()
+            .find(|field| field.name == field_name)
+            .map(|field| field.value.clone())
+    }
+
+    /// Like `get_value`, but None for the empty values and `-` Windows logs for unset fields.
+    pub fn get_present_value(&self, field_name: &str) -> Option<String> {
+        self.get_value(field_name).filter(|value| !value.is_empty() && value != "-")
+    }
+}
+
+/// Events from providers with their own schema, such as the event log's 1100 and 1102, put
+/// their fields in one element named for the event under `UserData` rather than `EventData`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UserData(HashMap<String, HashMap<String, String>>);
+
+impl UserData {
+    pub fn get_present_value(&self, field_name: &str) -> Option<String> {
+        self.0
+            .values()
+            .find_map(|fields| fields.get(field_name))
+            .filter(|value| !value.is_empty() && *value != "-")
+            .cloned()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventDataField {
+    #[serde(rename = "@Name")]
+    pub name: String,
+
+    #[serde(rename = "$text", default)]
+    pub value: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_system_section() {
+        let xml = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2026-02-27T05:22:35.4610070Z'/><EventRecordID>48211</EventRecordID><Correlation ActivityID='{a1f3c2d4-7b9e-4f10-8c2d-5e6f7a8b9c0d}'/><Execution ProcessID='1008' ThreadID='1120'/><Channel>Security</Channel><Computer>jordans-desktop</Computer><Security/></System><EventData><Data Name='TargetUserSid'>S-1-5-21-1</Data></EventData></Event>";
+        let event: Event = quick_xml::de::from_str(xml).unwrap();
+
+        let system = event.system;
+        assert_eq!(system.event_id, 4624);
+        assert_eq!(system.provider.as_ref().and_then(|provider| provider.name.as_deref()), Some("Microsoft-Windows-Security-Auditing"));
+        assert_eq!(system.time_created(), Some("2026-02-27T05:22:35.461007Z".parse().unwrap()));
+        assert_eq!(system.event_record_id, Some(48211));
+        assert_eq!(system.correlation.as_ref().and_then(|correlation| correlation.activity_id.as_deref()), Some("{a1f3c2d4-7b9e-4f10-8c2d-5e6f7a8b9c0d}"));
+        assert_eq!(system.execution.as_ref().and_then(|execution| execution.process_id), Some(1008));
+        assert_eq!(system.channel.as_deref(), Some("Security"));
+        assert_eq!(system.computer.as_deref(), Some("jordans-desktop"));
+        assert_eq!(system.to_string(), "4624 #48211 from Microsoft-Windows-Security-Auditing in Security on jordans-desktop (pid 1008, activity {a1f3c2d4-7b9e-4f10-8c2d-5e6f7a8b9c0d})");
+    }
+
+    #[test]
+    fn system_fields_are_optional() {
+        let event: Event = quick_xml::de::from_str("<Event><System><EventID>4647</EventID><Correlation/></System></Event>").unwrap();
+        assert_eq!(event.system.event_id, 4647);
+        assert_eq!(event.system.time_created(), None);
+        assert_eq!(event.system.event_record_id, None);
+        assert_eq!(event.system.correlation.as_ref().and_then(|correlation| correlation.activity_id.as_ref()), None);
+        assert!(event.event_data.is_none());
+        assert_eq!(event.system.to_string(), "4647");
+    }
+
+    #[test]
+    fn parses_user_data() {
+        let xml = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Eventlog'/><EventID>1102</EventID><Channel>Security</Channel></System><UserData><LogFileCleared xmlns='http://manifests.microsoft.com/win/2004/08/windows/eventlog'><SubjectUserSid>S-1-5-21-1</SubjectUserSid><SubjectUserName>jordan</SubjectUserName><SubjectDomainName>-</SubjectDomainName></LogFileCleared></UserData></Event>";
+        let event: Event = quick_xml::de::from_str(xml).unwrap();
+        assert!(event.event_data.is_none());
+        assert_eq!(event.get_present_value("SubjectUserSid").as_deref(), Some("S-1-5-21-1"));
+        assert_eq!(event.get_present_value("SubjectDomainName"), None);
+
+        let shutdown: Event = quick_xml::de::from_str("<Event><System><EventID>1100</EventID></System><UserData><ServiceShutdown xmlns='http://manifests.microsoft.com/win/2004/08/windows/eventlog'/></UserData></Event>").unwrap();
+        assert_eq!(shutdown.get_present_value("SubjectUserSid"), None);
+    }
+}
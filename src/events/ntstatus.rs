@@ -0,0 +1,61 @@
+//! Readable reasons for the NTSTATUS codes in the `Status` and `SubStatus` fields of failed
+//! logons (4625).
+
+/// The reason for an NTSTATUS code such as `0xC000006A`, None for codes not in the table.
+pub fn describe(code: &str) -> Option<&'static str> {
+    let code = u32::from_str_radix(code.trim().trim_start_matches("0x").trim_start_matches("0X"), 16).ok()?;
+    let reason = match code {
+        0xC000005E => "No logon servers are available",
+        0xC0000064 => "User name does not exist",
+        0xC000006A => "Wrong password",
+        0xC000006C => "Password does not meet the password policy",
+        0xC000006D => "Bad user name or password",
+        0xC000006E => "Account restriction",
+        0xC000006F => "Logon outside of the allowed hours",
+        0xC0000070 => "Logon from an unauthorized workstation",
+        0xC0000071 => "Password expired",
+        0xC0000072 => "Account disabled",
+        0xC00000DC => "Domain controller is in the wrong state",
+        0xC0000133 => "Clock out of sync with the domain controller",
+        0xC000015B => "Logon type not granted to the user",
+        0xC000018C => "Trust relationship between the device and the domain failed",
+        0xC0000192 => "Netlogon service is not started",
+        0xC0000193 => "Account expired",
+        0xC0000224 => "Password must be changed at next logon",
+        0xC0000234 => "Account locked out",
+        0xC00002EE => "An error occurred during logon",
+        0xC0000371 => "Local account store does not contain secret material for the account",
+        0xC0000380 => "Smart card is blocked",
+        0xC0000413 => "Blocked by the authentication firewall",
+        _ => return None
+    };
+    Some(reason)
+}
+
+/// The most specific reason for a failed logon, from `SubStatus` when it is set.
+pub fn failure_reason(status: Option<&str>, sub_status: Option<&str>) -> Option<&'static str> {
+    sub_status.and_then(describe).or_else(|| status.and_then(describe))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_codes_are_described() {
+        assert_eq!(describe("0xC000006A"), Some("Wrong password"));
+        assert_eq!(describe("0xc0000234"), Some("Account locked out"));
+        assert_eq!(describe(" 0xC0000064 "), Some("User name does not exist"));
+        assert_eq!(describe("0x0"), None);
+        assert_eq!(describe("0xC0FFEE00"), None);
+        assert_eq!(describe("%%2313"), None);
+    }
+
+    #[test]
+    fn sub_status_is_preferred() {
+        assert_eq!(failure_reason(Some("0xC000006D"), Some("0xC000006A")), Some("Wrong password"));
+        assert_eq!(failure_reason(Some("0xC000006D"), Some("0x0")), Some("Bad user name or password"));
+        assert_eq!(failure_reason(Some("0xC000006D"), None), Some("Bad user name or password"));
+        assert_eq!(failure_reason(None, None), None);
+    }
+}
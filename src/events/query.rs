@@ -0,0 +1,226 @@
+//! Builds the queries watchers subscribe with: XPath filters on event IDs and `EventData`
+//! fields, and structured `<QueryList>` queries selecting from several channels at once.
+
+use std::fmt;
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::state::bookmark::with_look_back;
+
+/// An XPath filter matching any of `event_ids`, optionally restricted by `EventData` fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventFilter {
+    event_ids: Vec<u32>,
+    data: Vec<(String, Vec<String>)>,
+}
+
+impl EventFilter {
+    pub fn ids(event_ids: &[u32]) -> Self {
+        Self { event_ids: event_ids.to_vec(), data: Vec::new() }
+    }
+
+    /// Also requires the `EventData` field `name` to equal one of `values`. Each call adds
+    /// another field that has to match. Values that can't be quoted are left out, see `literal`.
+    pub fn data_any<T: fmt::Display>(mut self, name: &str, values: impl IntoIterator<Item = T>) -> Self {
+        let values = values
+            .into_iter()
+            .map(|value| value.to_string())
+            .filter(|value| {
+                let quotable = literal(value).is_some();
+                if !quotable {
+                    warn!(action="event_query", "Leaving {} value {} out of the query, it has both ' and \" in it", name, value);
+                }
+                quotable
+            })
+            .collect();
+        self.data.push((name.to_string(), values));
+        self
+    }
+
+    /// The `Event[...]` query for this filter alone.
+    pub fn xpath(&self) -> String {
+        format!("Event[{}]", self.predicate())
+    }
+
+    fn predicate(&self) -> String {
+        let event_ids = self.event_ids
+            .iter()
+            .map(|event_id| format!("EventID='{}'", event_id))
+            .collect::<Vec<String>>()
+            .join(" or ");
+        let mut predicates = vec![format!("System[({})]", event_ids)];
+        for (name, values) in &self.data {
+            let values = values
+                .iter()
+                .filter_map(|value| Some(format!("Data[@Name={}]={}", literal(name)?, literal(value)?)))
+                .collect::<Vec<String>>()
+                .join(" or ");
+            predicates.push(format!("EventData[{}]", values));
+        }
+        predicates.join(" and ")
+    }
+}
+
+/// Quotes a value as an XPath string literal. XPath has no escapes, so a value containing `'`
+/// is quoted with `"` instead. None for a value with both, the event log's XPath has no
+/// `concat()` to build it from parts.
+fn literal(value: &str) -> Option<String> {
+    match (value.contains('\''), value.contains('"')) {
+        (false, _) => Some(format!("'{}'", value)),
+        (true, false) => Some(format!("\"{}\"", value)),
+        (true, true) => None
+    }
+}
+
+/// The `Event[...]` query matching events that match any of `filters`.
+pub fn any_of(filters: &[EventFilter]) -> String {
+    if let [filter] = filters {
+        return filter.xpath();
+    }
+    let predicates = filters
+        .iter()
+        .map(|filter| match filter.data.is_empty() {
+            true => filter.predicate(),
+            false => format!("({})", filter.predicate())
+        })
+        .collect::<Vec<String>>()
+        .join(" or ");
+    format!("Event[({})]", predicates)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub channel: String,
+    pub xpath: String,
+}
+
+/// A structured query, one `<Select>` for each channel and XPath query.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryList {
+    pub selects: Vec<Select>,
+}
+
+impl QueryList {
+    /// A query list with a single `<Select>`, the same as subscribing to `channel` with `xpath`.
+    pub fn channel(channel: &str, xpath: &str) -> Self {
+        Self::default().select(channel, xpath)
+    }
+
+    pub fn select(mut self, channel: &str, xpath: &str) -> Self {
+        self.selects.push(Select { channel: channel.to_string(), xpath: xpath.to_string() });
+        self
+    }
+
+    /// Restricts each select to events raised within `look_back`. None if the window can't
+    /// be added to one of them, reading it from the oldest record would replay the whole log.
+    pub fn with_look_back(&self, look_back: Duration) -> Option<Self> {
+        let selects = self.selects
+            .iter()
+            .map(|select| Some(Select { channel: select.channel.clone(), xpath: with_look_back(&select.xpath, look_back)? }))
+            .collect::<Option<Vec<Select>>>()?;
+        Some(Self { selects })
+    }
+}
+
+impl fmt::Display for QueryList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<QueryList><Query Id=\"0\">")?;
+        for select in &self.selects {
+            write!(f, "<Select Path=\"{}\">{}</Select>", escape(&select.channel), escape(&select.xpath))?;
+        }
+        write!(f, "</Query></QueryList>")
+    }
+}
+
+/// Escapes text for an XML attribute or element, e.g. the `<=` in a look-back filter.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_build_xpath() {
+        assert_eq!(EventFilter::ids(&[4647]).xpath(), "Event[System[(EventID='4647')]]");
+        assert_eq!(
+            EventFilter::ids(&[4800, 4801]).xpath(),
+            "Event[System[(EventID='4800' or EventID='4801')]]"
+        );
+        assert_eq!(
+            EventFilter::ids(&[4625]).data_any("LogonType", [2, 10]).data_any("Status", ["0xc000006d"]).xpath(),
+            "Event[System[(EventID='4625')] and EventData[Data[@Name='LogonType']='2' or Data[@Name='LogonType']='10'] and EventData[Data[@Name='Status']='0xc000006d']]"
+        );
+    }
+
+    #[test]
+    fn data_values_are_quoted() {
+        assert_eq!(
+            EventFilter::ids(&[4624]).data_any("TargetUserName", ["O'Brien"]).xpath(),
+            "Event[System[(EventID='4624')] and EventData[Data[@Name='TargetUserName']=\"O'Brien\"]]"
+        );
+        assert_eq!(literal("say \"it's\""), None);
+        assert_eq!(
+            EventFilter::ids(&[4624]).data_any("TargetUserName", ["say \"it's\"", "say \"hi\""]).xpath(),
+            "Event[System[(EventID='4624')] and EventData[Data[@Name='TargetUserName']='say \"hi\"']]"
+        );
+
+        // The query list decodes back to the same XPath
+        let xpath = EventFilter::ids(&[4624]).data_any("TargetUserName", ["O'Brien", "say \"hi\""]).xpath();
+        let query = QueryList::channel("Security", &xpath).to_string();
+        let select = query.split_once("\">").unwrap().1.split_once("\">").unwrap().1.split_once("</Select>").unwrap().0;
+        assert_eq!(quick_xml::escape::unescape(select).unwrap(), xpath);
+        assert!(xpath.contains("=\"O'Brien\" or ") && xpath.ends_with("='say \"hi\"']]"));
+    }
+
+    #[test]
+    fn any_of_combines_filters() {
+        assert_eq!(any_of(&[EventFilter::ids(&[1102])]), "Event[System[(EventID='1102')]]");
+        assert_eq!(
+            any_of(&[EventFilter::ids(&[4624]).data_any("LogonType", [2]), EventFilter::ids(&[4647])]),
+            "Event[((System[(EventID='4624')] and EventData[Data[@Name='LogonType']='2']) or System[(EventID='4647')])]"
+        );
+    }
+
+    #[test]
+    fn query_list_selects_from_each_channel() {
+        let query = QueryList::channel("Security", "Event[System[(EventID='4624')]]")
+            .select("Microsoft-Windows-TerminalServices-LocalSessionManager/Operational", "Event[System[(EventID='21' or EventID='23')]]")
+            .select("System", "*");
+        assert_eq!(
+            query.to_string(),
+            concat!(
+                "<QueryList><Query Id=\"0\">",
+                "<Select Path=\"Security\">Event[System[(EventID='4624')]]</Select>",
+                "<Select Path=\"Microsoft-Windows-TerminalServices-LocalSessionManager/Operational\">Event[System[(EventID='21' or EventID='23')]]</Select>",
+                "<Select Path=\"System\">*</Select>",
+                "</Query></QueryList>"
+            )
+        );
+    }
+
+    #[test]
+    fn look_back_is_added_to_each_select_and_escaped() {
+        let query = QueryList::channel("Security", "Event[System[(EventID='4647')]]")
+            .select("Microsoft-Windows-User Profile Service/Operational", "*[System[(EventID='1')]]")
+            .with_look_back(Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(
+            query.to_string(),
+            concat!(
+                "<QueryList><Query Id=\"0\">",
+                "<Select Path=\"Security\">Event[(System[(EventID='4647')]) and System[TimeCreated[timediff(@SystemTime) &lt;= 60000]]]</Select>",
+                "<Select Path=\"Microsoft-Windows-User Profile Service/Operational\">*[(System[(EventID='1')]) and System[TimeCreated[timediff(@SystemTime) &lt;= 60000]]]</Select>",
+                "</Query></QueryList>"
+            )
+        );
+    }
+
+    #[test]
+    fn look_back_needs_every_select_bracketed() {
+        let query = QueryList::channel("Security", "Event[System[(EventID='4647')]]").select("System", "Event/System");
+        assert_eq!(query.with_look_back(Duration::from_secs(60)), None);
+    }
+}
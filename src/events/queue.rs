@@ -0,0 +1,111 @@
+//! Hands events from the event source's threads to the service loop. Callbacks only parse
+//! and enqueue, enrichment runs on the tokio runtime in the order events arrived. When the
+//! queue is full events are dropped and counted rather than blocking Windows' thread pool.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, Receiver, Sender, error::TrySendError};
+
+use super::Event;
+
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Which watcher delivered an event, and so which handler it goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Watcher {
+    LogonLogoff,
+    Presence,
+    FailedLogon,
+    AuditIntegrity,
+}
+
+impl Watcher {
+    /// Names the watcher's subscription and its bookmark file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Watcher::LogonLogoff => "logon_logoff",
+            Watcher::Presence => "presence",
+            Watcher::FailedLogon => "failed_logon",
+            Watcher::AuditIntegrity => "audit_integrity",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct QueuedEvent {
+    pub watcher: Watcher,
+    pub event: Event,
+}
+
+#[derive(Clone)]
+pub struct EventQueue {
+    tx: Sender<QueuedEvent>,
+    dropped: Arc<Mutex<HashMap<Watcher, u64>>>,
+}
+
+pub fn event_queue(capacity: usize) -> (EventQueue, Receiver<QueuedEvent>) {
+    let (tx, rx) = mpsc::channel(capacity);
+    (EventQueue { tx, dropped: Arc::default() }, rx)
+}
+
+impl EventQueue {
+    /// Queues the event without blocking, returning false if it was dropped.
+    pub fn push(&self, watcher: Watcher, event: Event) -> bool {
+        match self.tx.try_send(QueuedEvent { watcher, event }) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                *self.dropped.lock().unwrap().entry(watcher).or_default() += 1;
+                false
+            },
+            // The service loop has stopped
+            Err(TrySendError::Closed(_)) => false
+        }
+    }
+
+    /// Events dropped because the queue was full since the last call, by the watcher that
+    /// delivered them.
+    pub fn take_dropped(&self) -> HashMap<Watcher, u64> {
+        std::mem::take(&mut *self.dropped.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::event;
+
+    #[test]
+    fn events_are_queued_in_order() {
+        let (queue, mut rx) = event_queue(4);
+        assert!(queue.push(Watcher::LogonLogoff, event(4624, &[])));
+        assert!(queue.push(Watcher::Presence, event(4800, &[])));
+
+        let first = rx.try_recv().unwrap();
+        assert_eq!((first.watcher, first.event.system.event_id), (Watcher::LogonLogoff, 4624));
+        assert_eq!(rx.try_recv().unwrap().watcher, Watcher::Presence);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn overflow_is_counted() {
+        let (queue, mut rx) = event_queue(2);
+        for _ in 0..4 {
+            queue.push(Watcher::LogonLogoff, event(4624, &[]));
+        }
+        queue.push(Watcher::Presence, event(4800, &[]));
+        assert_eq!(queue.take_dropped(), HashMap::from([(Watcher::LogonLogoff, 2), (Watcher::Presence, 1)]));
+        assert!(queue.take_dropped().is_empty());
+
+        rx.try_recv().unwrap();
+        assert!(queue.push(Watcher::LogonLogoff, event(4647, &[])));
+        assert!(queue.take_dropped().is_empty());
+
+        drop(rx);
+        assert!(!queue.push(Watcher::LogonLogoff, event(4624, &[])));
+        assert!(queue.take_dropped().is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+//! A periodic `heartbeat` record so a quiet log can be told apart from a dead agent or a
+//! device that is turned off.
+
+use chrono::{DateTime, Utc};
+use tokio::time::{Duration, Instant, Interval, MissedTickBehavior, interval_at};
+use tracing::error;
+
+use crate::records::{self, CurrentUserRecord, HeartbeatRecord};
+use crate::platform::SessionProvider;
+
+/// What the service loop has done since it started.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgentStats {
+    pub events_processed: u64,
+    pub events_dropped: u64,
+    /// When Windows raised the last event processed.
+    pub last_event_time: Option<DateTime<Utc>>,
+}
+
+pub struct Heartbeat {
+    started: Instant,
+    interval: Interval,
+}
+
+impl Heartbeat {
+    /// The first heartbeat is one `period` after starting. If the device sleeps through some,
+    /// the next one is sent straight away on waking and the rest stay on the same schedule.
+    pub fn new(period: Duration) -> Self {
+        let started = Instant::now();
+        let mut interval = interval_at(started + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        Self { started, interval }
+    }
+
+    /// Waits for the next heartbeat and returns how long the agent has been running.
+    pub async fn tick(&mut self) -> Duration {
+        self.interval.tick().await;
+        self.started.elapsed()
+    }
+}
+
+pub fn emit_heartbeat(uptime: Duration, stats: &AgentStats, sessions: &dyn SessionProvider) {
+    let console_user = match sessions.get_user_info() {
+        Ok(user_info) => Some(CurrentUserRecord::from(user_info)),
+        Err(err) => {
+            error!(action="heartbeat", "Unable to retrieve user info: {}", err);
+            None
+        }
+    };
+    records::emit(&HeartbeatRecord {
+        agent_version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: uptime.as_secs(),
+        events_processed: stats.events_processed,
+        events_dropped: stats.events_dropped,
+        last_event_time: stats.last_event_time,
+        console_user,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::CurrentUserInfo;
+    use crate::platform::fake::FakeSessionProvider;
+    use crate::test_support::{capture_records, records_with_action};
+
+    #[tokio::test(start_paused = true)]
+    async fn heartbeats_every_period() {
+        let mut heartbeat = Heartbeat::new(Duration::from_secs(300));
+        assert_eq!(heartbeat.tick().await, Duration::from_secs(300));
+        assert_eq!(heartbeat.tick().await, Duration::from_secs(600));
+        assert_eq!(heartbeat.tick().await, Duration::from_secs(900));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn missed_heartbeats_are_skipped() {
+        let mut heartbeat = Heartbeat::new(Duration::from_secs(300));
+        assert_eq!(heartbeat.tick().await, Duration::from_secs(300));
+
+        // Asleep through two heartbeats
+        tokio::time::advance(Duration::from_secs(700)).await;
+        assert_eq!(heartbeat.tick().await, Duration::from_secs(1000));
+        assert_eq!(heartbeat.tick().await, Duration::from_secs(1200));
+    }
+
+    #[test]
+    fn heartbeat_has_agent_state() {
+        let sessions = FakeSessionProvider::default();
+        *sessions.user.lock().unwrap() = Some(CurrentUserInfo {
+            sid: "S-1-12-1-2991438786-1147252871-734652841-3570430303".to_string(),
+            username: "JordanGomes".to_string(),
+            user_type: "AzureAD".to_string(),
+            azure_ad_object_id: None,
+        });
+        let stats = AgentStats { events_processed: 12, events_dropped: 1, last_event_time: Some("2026-02-27T05:22:35Z".parse().unwrap()) };
+
+        let records = capture_records(|| emit_heartbeat(Duration::from_secs(3600), &stats, &sessions));
+
+        let heartbeat = records_with_action(&records, "heartbeat");
+        assert_eq!(heartbeat.len(), 1);
+        assert_eq!(heartbeat[0]["agent_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(heartbeat[0]["uptime_secs"], 3600);
+        assert_eq!(heartbeat[0]["events_processed"], 12);
+        assert_eq!(heartbeat[0]["events_dropped"], 1);
+        assert_eq!(heartbeat[0]["last_event_time"], "2026-02-27T05:22:35Z");
+        assert_eq!(heartbeat[0]["console_user"]["logged_in"], true);
+        assert_eq!(heartbeat[0]["console_user"]["username"], "JordanGomes");
+    }
+}
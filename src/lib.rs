@@ -0,0 +1,20 @@
+//! The service core, written against the `platform` traits so it builds and is tested off
+//! Windows. `windows_api` implements them on Windows, `main.rs` runs the core as the service
+//! and provides the `replay` and `sessions` commands everywhere.
+
+pub mod config;
+pub mod events;
+pub mod heartbeat;
+pub mod logging;
+pub mod platform;
+pub mod records;
+pub mod replay;
+pub mod service;
+pub mod sessions;
+pub mod sinks;
+pub mod state;
+pub mod tenants;
+#[cfg(windows)]
+pub mod windows_api;
+#[cfg(test)]
+mod test_support;
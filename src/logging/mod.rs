@@ -0,0 +1,102 @@
+use std::fmt;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, MakeWriter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+
+pub mod rotation;
+
+/// Field `records::emit` puts the serialized record in, merged into the top level of the line.
+pub const RECORD_FIELD: &str = "record";
+
+/// The JSON line format every cua record is written in, shared by the service and `cua replay`.
+pub fn json_layer<S, W>(writer: W) -> impl Layer<S> + Send + Sync
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .event_format(JsonFormat)
+        .with_writer(writer)
+}
+
+pub fn json_subscriber<W>(writer: W) -> impl Subscriber + Send + Sync
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(json_layer(writer))
+}
+
+/// One JSON object per line - timestamp, level, the event's fields and then the target.
+pub struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+{
+    fn format_event(&self, _ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        writeln!(writer, "{}", Value::Object(event_json(event).0))
+    }
+}
+
+/// Converts an event to the object written to the log, and whether it carried a record.
+pub fn event_json(event: &Event<'_>) -> (Map<String, Value>, bool) {
+    let metadata = event.metadata();
+    let mut visitor = JsonVisitor::default();
+    event.record(&mut visitor);
+
+    let mut json = Map::new();
+    json.insert("timestamp".to_string(), Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true).into());
+    json.insert("level".to_string(), metadata.level().as_str().into());
+    json.extend(visitor.fields);
+    json.insert("target".to_string(), metadata.target().into());
+    (json, visitor.is_record)
+}
+
+#[derive(Default)]
+struct JsonVisitor {
+    fields: Map<String, Value>,
+    is_record: bool,
+}
+
+impl Visit for JsonVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == RECORD_FIELD
+            && let Ok(Value::Object(record)) = serde_json::from_str(value) {
+            self.fields.extend(record);
+            self.is_record = true;
+            return;
+        }
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields.insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
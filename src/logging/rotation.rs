@@ -0,0 +1,354 @@
+//! Rotation for `cua.log`. The file is rotated by size and optionally at midnight UTC, rotated
+//! files are renamed to `cua.log.<time>`, gzipped if enabled, and the oldest are removed past
+//! `max_files`. Rotation happens inside `write`, which only the non-blocking writer's worker
+//! thread calls, so no line is lost or split while the file is swapped. Compressing and
+//! pruning happen on a thread of their own so writes aren't held up, and nothing is logged
+//! from either, failures are kept in `RotationErrors` for the service to log.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::config::LogRotationConfig;
+
+/// How long to keep writing to the current file after a failed rotation before trying again.
+const RETRY_AFTER: TimeDelta = TimeDelta::minutes(1);
+
+/// Rotation failures waiting to be logged. Logging them from the writer would write to the
+/// file being rotated from inside its own `write`.
+#[derive(Debug, Clone, Default)]
+pub struct RotationErrors(Arc<Mutex<Vec<String>>>);
+
+impl RotationErrors {
+    pub fn push(&self, error: String) {
+        self.0.lock().unwrap().push(error);
+    }
+
+    /// The errors since the last call.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+pub struct RotatingFile {
+    dir: PathBuf,
+    name: String,
+    config: LogRotationConfig,
+    file: File,
+    size: u64,
+    opened: NaiveDate,
+    retry_at: Option<DateTime<Utc>>,
+    errors: RotationErrors,
+    /// Rotated files are sent here to be compressed and pruned.
+    archive_thread: Option<(Sender<PathBuf>, JoinHandle<()>)>,
+}
+
+impl RotatingFile {
+    /// Opens `dir/name` for appending, creating the directory if needed.
+    pub fn open(dir: &Path, name: &str, config: &LogRotationConfig) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = open_append(&dir.join(name))?;
+        let metadata = file.metadata()?;
+        // A file left from a previous day is rotated on the first write
+        let opened = metadata.modified().map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now());
+        let errors = RotationErrors::default();
+        let archive_thread = spawn_archive_thread(dir, name, config, errors.clone())?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            config: config.clone(),
+            file,
+            size: metadata.len(),
+            opened: opened.date_naive(),
+            retry_at: None,
+            errors,
+            archive_thread: Some(archive_thread),
+        })
+    }
+
+    /// Where rotation failures are kept until they are logged.
+    pub fn errors(&self) -> RotationErrors {
+        self.errors.clone()
+    }
+
+    /// Opens `name` in the first of `dirs` that works. The errors for the folders that
+    /// didn't are returned so they can be logged once logging is set up.
+    pub fn open_first(dirs: &[PathBuf], name: &str, config: &LogRotationConfig) -> (Option<Self>, Vec<String>) {
+        let mut errors = Vec::new();
+        for dir in dirs {
+            match Self::open(dir, name, config) {
+                Ok(file) => return (Some(file), errors),
+                Err(err) => errors.push(format!("Unable to open {} in {} - {}", name, dir.display(), err))
+            }
+        }
+        (None, errors)
+    }
+
+    fn write_at(&mut self, buf: &[u8], now: DateTime<Utc>) -> io::Result<usize> {
+        if self.size == 0 {
+            self.opened = now.date_naive();
+        } else if self.should_rotate(buf.len(), now)
+            && let Err(err) = self.rotate(now) {
+            self.retry_at = Some(now + RETRY_AFTER);
+            self.errors.push(format!("Unable to rotate {} - {}", self.name, err));
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn should_rotate(&self, len: usize, now: DateTime<Utc>) -> bool {
+        if self.retry_at.is_some_and(|retry_at| now < retry_at) {
+            return false;
+        }
+        let too_big = self.config.max_bytes > 0 && self.size + len as u64 > self.config.max_bytes;
+        let new_day = self.config.daily && now.date_naive() != self.opened;
+        too_big || new_day
+    }
+
+    fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        self.file.flush()?;
+        let path = self.dir.join(&self.name);
+        let rotated = self.rotated_path(now);
+        fs::rename(&path, &rotated)?;
+        self.file = open_append(&path)?;
+        self.size = 0;
+        self.opened = now.date_naive();
+        self.retry_at = None;
+
+        if let Some((archive, _)) = &self.archive_thread {
+            _ = archive.send(rotated);
+        }
+        Ok(())
+    }
+
+    /// `name.<time>`, which sorts oldest first. Bumped a microsecond at a time if taken.
+    fn rotated_path(&self, mut now: DateTime<Utc>) -> PathBuf {
+        loop {
+            let path = self.dir.join(format!("{}.{}", self.name, now.format("%Y%m%d-%H%M%S-%6f")));
+            if !path.exists() && !gz_path(&path).exists() {
+                return path;
+            }
+            now += TimeDelta::microseconds(1);
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_at(buf, Utc::now())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for RotatingFile {
+    fn drop(&mut self) {
+        // Dropping the sender ends the thread once it has archived what was already rotated
+        if let Some((archive, thread)) = self.archive_thread.take() {
+            drop(archive);
+            _ = thread.join();
+        }
+    }
+}
+
+/// Compresses (if enabled) each rotated file it is sent and then prunes the old ones, until
+/// the sender is dropped. The log has moved on by then, so failures are only reported.
+fn spawn_archive_thread(dir: &Path, name: &str, config: &LogRotationConfig, errors: RotationErrors) -> io::Result<(Sender<PathBuf>, JoinHandle<()>)> {
+    let (archive, rotated) = mpsc::channel::<PathBuf>();
+    let (dir, name, config) = (dir.to_path_buf(), name.to_string(), config.clone());
+    let thread = thread::Builder::new().name(format!("{}-archive", name)).spawn(move || {
+        for path in rotated {
+            if config.compress
+                && let Err(err) = compress(&path) {
+                errors.push(format!("Unable to compress {} - {}", path.display(), err));
+            }
+            if let Err(err) = prune(&dir, &name, config.max_files) {
+                errors.push(format!("Unable to remove old log files - {}", err));
+            }
+        }
+    })?;
+    Ok((archive, thread))
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    let mut gz = path.as_os_str().to_owned();
+    gz.push(".gz");
+    PathBuf::from(gz)
+}
+
+/// Gzips `path` to `path.gz` and removes the original.
+fn compress(path: &Path) -> io::Result<PathBuf> {
+    let gz = gz_path(path);
+    let mut encoder = GzEncoder::new(File::create(&gz)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(path)?;
+    Ok(gz)
+}
+
+/// Rotated copies of `name` in `dir`, oldest first.
+pub fn rotated_files(dir: &Path, name: &str) -> io::Result<Vec<PathBuf>> {
+    let prefix = format!("{}.", name);
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        if file_name.to_str().and_then(|file_name| file_name.strip_prefix(&prefix)).is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit())) {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Removes the oldest rotated files so at most `max_files` are left, returning how many went.
+pub fn prune(dir: &Path, name: &str, max_files: usize) -> io::Result<usize> {
+    let files = rotated_files(dir, name)?;
+    let excess = files.len().saturating_sub(max_files);
+    for file in &files[..excess] {
+        fs::remove_file(file)?;
+    }
+    Ok(excess)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn file_names(files: &[PathBuf]) -> Vec<String> {
+        files.iter().map(|file| file.file_name().unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    fn read_gz(path: &Path) -> String {
+        let mut contents = String::new();
+        GzDecoder::new(File::open(path).unwrap()).read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn size_rotation_compresses_and_keeps_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogRotationConfig { max_bytes: 16, daily: false, max_files: 2, compress: true };
+        let mut log = RotatingFile::open(dir.path(), "cua.log", &config).unwrap();
+        let start = Utc.with_ymd_and_hms(2026, 2, 27, 5, 22, 35).unwrap();
+        for n in 0..7 {
+            log.write_at(format!("line {}\n", n).as_bytes(), start + TimeDelta::seconds(n)).unwrap();
+        }
+        let errors = log.errors();
+        // Waits for the archive thread
+        drop(log);
+        assert!(errors.take().is_empty());
+
+        // Two lines fit in each file, the file holding lines 0-1 has been pruned
+        let rotated = rotated_files(dir.path(), "cua.log").unwrap();
+        assert_eq!(file_names(&rotated), vec!["cua.log.20260227-052239-000000.gz", "cua.log.20260227-052241-000000.gz"]);
+        assert_eq!(read_gz(&rotated[0]), "line 2\nline 3\n");
+        assert_eq!(read_gz(&rotated[1]), "line 4\nline 5\n");
+        assert_eq!(fs::read_to_string(dir.path().join("cua.log")).unwrap(), "line 6\n");
+    }
+
+    #[test]
+    fn daily_rotation_happens_at_midnight() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogRotationConfig { max_bytes: 0, daily: true, max_files: 10, compress: false };
+        let mut log = RotatingFile::open(dir.path(), "cua.log", &config).unwrap();
+        let evening = Utc.with_ymd_and_hms(2026, 2, 27, 23, 59, 0).unwrap();
+        log.write_at(b"first\n", evening).unwrap();
+        log.write_at(b"second\n", evening + TimeDelta::seconds(30)).unwrap();
+        log.write_at(b"third\n", evening + TimeDelta::minutes(2)).unwrap();
+        drop(log);
+
+        let rotated = rotated_files(dir.path(), "cua.log").unwrap();
+        assert_eq!(file_names(&rotated), vec!["cua.log.20260228-000100-000000"]);
+        assert_eq!(fs::read_to_string(&rotated[0]).unwrap(), "first\nsecond\n");
+        assert_eq!(fs::read_to_string(dir.path().join("cua.log")).unwrap(), "third\n");
+    }
+
+    #[test]
+    fn rotations_in_the_same_instant_get_their_own_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogRotationConfig { max_bytes: 1, daily: false, max_files: 10, compress: false };
+        let mut log = RotatingFile::open(dir.path(), "cua.log", &config).unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 2, 27, 5, 22, 35).unwrap();
+        for line in ["a\n", "b\n", "c\n"] {
+            log.write_at(line.as_bytes(), now).unwrap();
+        }
+
+        let rotated = rotated_files(dir.path(), "cua.log").unwrap();
+        assert_eq!(file_names(&rotated), vec!["cua.log.20260227-052235-000000", "cua.log.20260227-052235-000001"]);
+        assert_eq!(fs::read_to_string(&rotated[1]).unwrap(), "b\n");
+    }
+
+    #[test]
+    fn rotation_errors_are_kept_for_the_service() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogRotationConfig { max_bytes: 1, daily: false, max_files: 10, compress: false };
+        let mut log = RotatingFile::open(&dir.path().join("logs"), "cua.log", &config).unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 2, 27, 5, 22, 35).unwrap();
+        log.write_at(b"a\n", now).unwrap();
+        fs::remove_dir_all(dir.path().join("logs")).unwrap();
+
+        // Writing carries on to the open file, and isn't retried until RETRY_AFTER
+        log.write_at(b"b\n", now).unwrap();
+        log.write_at(b"c\n", now + TimeDelta::seconds(1)).unwrap();
+        let errors = log.errors().take();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].starts_with("Unable to rotate cua.log - "), "{}", errors[0]);
+        assert!(log.errors().take().is_empty());
+    }
+
+    #[test]
+    fn open_first_falls_back_to_the_next_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let not_a_dir = dir.path().join("cua.toml");
+        fs::write(&not_a_dir, "").unwrap();
+        let config = LogRotationConfig::default();
+
+        let (log, errors) = RotatingFile::open_first(&[not_a_dir.clone(), dir.path().join("logs")], "cua.log", &config);
+        assert_eq!(log.unwrap().dir, dir.path().join("logs"));
+        assert_eq!(errors.len(), 1);
+
+        let (log, errors) = RotatingFile::open_first(&[not_a_dir], "cua.log", &config);
+        assert!(log.is_none());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn prune_removes_oldest_and_leaves_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "cua.log",
+            "cua.log.20260101-000000-000000.gz",
+            "cua.log.20260102-000000-000000",
+            "cua.log.20260103-000000-000000.gz",
+            "cua.log.tmp",
+            "cua.toml",
+        ] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        assert_eq!(prune(dir.path(), "cua.log", 1).unwrap(), 2);
+        assert_eq!(file_names(&rotated_files(dir.path(), "cua.log").unwrap()), vec!["cua.log.20260103-000000-000000.gz"]);
+        for name in ["cua.log", "cua.log.tmp", "cua.toml"] {
+            assert!(dir.path().join(name).exists(), "{} was removed", name);
+        }
+        assert_eq!(prune(dir.path(), "cua.log", 1).unwrap(), 0);
+    }
+}
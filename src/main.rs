@@ -1,6 +1,13 @@
-use std::{env, sync::mpsc::Receiver};
-use tokio::{time::{Duration, interval}};
-use tracing::{error, info, warn};
+
+use std::path::Path;
+use std::process::ExitCode;
+#[cfg(windows)]
+use tokio::time::Duration;
+#[cfg(windows)]
+use tracing::{error, info};
+#[cfg(windows)]
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt};
+#[cfg(windows)]
 use windows_service::{
     define_windows_service,
     service::{
@@ -13,13 +20,20 @@ use windows_service::{
 
 
 
-mod windows_api;
+#[cfg(windows)]
 mod service_helpers;
 
-use crate::windows_api::event_watcher::{Event};
+use cua::config::{Config, LoadedConfig, LogonLogoffConfig};
+use cua::{platform, replay, sessions};
+#[cfg(windows)]
+use cua::{config, logging, service, sinks, windows_api};
+#[cfg(windows)]
+use cua::service::ServiceMessage;
 
 // Main service entry point
+#[cfg(windows)]
 define_windows_service!(ffi_service_main, service_main);
+#[cfg(windows)]
 fn service_main(_arguments: Vec<std::ffi::OsString>) {
     if let Err(e) = run_service() {
         error!("Service failed: {:?}", e);
@@ -28,30 +42,58 @@ fn service_main(_arguments: Vec<std::ffi::OsString>) {
 
 
 
+#[cfg(windows)]
 fn run_service() -> Result<()> {
-    // Set up logging
-    let mut path = env::current_exe().unwrap_or("C:\\ProgramData\\cua\\cua.exe".into());
-    _ = path.pop();
-    _ = path.pop();
-    let file_appender = tracing_appender::rolling::never(path, "cua.log");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-
-    tracing_subscriber::fmt()
-        .json()
-        .with_target(true)          // Include the module path
-        .with_current_span(true)    // Include the current span
-        .with_span_list(true)     
-        .flatten_event(true)  // Include the full span hierarchy
-        .with_writer(non_blocking)
-        .init();
+    // Load config and set up logging
+    let loaded_config = Config::load();
+    let config = loaded_config.config;
+    // Fall back to the default folder if the configured one can't be used, and as a last
+    // resort keep running with only the sinks rather than failing to start
+    let mut log_dirs = vec![config.log_dir()];
+    if config.log_dir.is_some() {
+        log_dirs.push(config::default_log_dir());
+    }
+    let (log_file, log_errors) = logging::rotation::RotatingFile::open_first(&log_dirs, "cua.log", &config.log_rotation);
+    // Logged by the service loop, the file can't log its own rotation failures
+    let rotation_errors = log_file.as_ref().map(logging::rotation::RotatingFile::errors).unwrap_or_default();
+    let log_writer: Box<dyn std::io::Write + Send> = match log_file {
+        Some(log_file) => Box::new(log_file),
+        None => Box::new(std::io::sink())
+    };
+    let (non_blocking, _guard) = tracing_appender::non_blocking(log_writer);
+
+    let (sink_layer, sink_handle) = sinks::start_from_config(&config).unzip();
+    let subscriber = tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(logging::json_layer(non_blocking))
+        .with(sink_layer);
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("Unable to set global tracing subscriber");
+
+    match &loaded_config.path {
+        Some(path) => info!(action="config_load", "Loaded config from {}", path.display()),
+        None => info!(action="config_load", "No config file found, using defaults")
+    };
+    for err in &loaded_config.errors {
+        error!(action="config_load", "{}", err);
+    }
+    for err in &log_errors {
+        error!(action="log_open", "{}", err);
+    }
 
     // Define service status
-    let (shutdown_tx, shutdown_rx) = std::sync::mpsc::channel();
+    let (service_tx, service_rx) = std::sync::mpsc::channel();
     let status_handle = service_control_handler::register(
         service_helpers::config::SERVICE_NAME,
         move |control_event| match control_event {
             ServiceControl::Stop => {
-                shutdown_tx.send(()).unwrap();
+                service_tx.send(ServiceMessage::Shutdown).unwrap();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::SessionChange(param) => {
+                if let Some(change) = windows_api::user_info::session_change(param) {
+                    _ = service_tx.send(ServiceMessage::SessionChange(change));
+                }
                 ServiceControlHandlerResult::NoError
             }
             _ => ServiceControlHandlerResult::NotImplemented,
@@ -60,7 +102,7 @@ fn run_service() -> Result<()> {
     status_handle.set_service_status(ServiceStatus {
         service_type: ServiceType::OWN_PROCESS,
         current_state: ServiceState::Running,
-        controls_accepted: ServiceControlAccept::STOP,
+        controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::SESSION_CHANGE,
         exit_code: ServiceExitCode::Win32(0),
         checkpoint: 0,
         wait_hint: Duration::from_secs(10),
@@ -68,7 +110,7 @@ fn run_service() -> Result<()> {
     })?;
     // Main service loop
     info!(action="service_startup", "Service is running...");
-    service_loop(shutdown_rx);
+    service::service_loop(&windows_api::platform(), &config, &rotation_errors, service_rx);
     info!(action="service_stopped", "Service is stopping...");
     status_handle.set_service_status(ServiceStatus {
         service_type: ServiceType::OWN_PROCESS,
@@ -80,61 +122,47 @@ fn run_service() -> Result<()> {
         process_id: None
     })?;
     info!(action="service_stopped", "Service is stopped");
+    // Last, so everything logged above still reaches the sinks
+    if let Some(sink_handle) = sink_handle {
+        sink_handle.shutdown();
+    }
     Ok(())
 }
 
-fn service_loop(shutdown_rx: Receiver<()>) {
-    const LOGON_LOGOFF_EVENT_XPATH: &str = "Event[((System[(EventID='4624')] and EventData[Data[@Name='LogonType']='2' or Data[@Name='LogonType']='7' or Data[@Name='LogonType']='10' or Data[@Name='LogonType']='11']) or System[(EventID='4647')])]";
-    const WHITELISTED_SID: [&str; 2] = ["S-1-5-96", "S-1-5-90"];
-
-    let logon_logoff_event_callback = Box::new(|event: Event| -> () {
-        match event.event_data {
-            Some(event_data) => {
-                let sid = event_data.get_value("TargetUserSid");
-                let username = event_data.get_value("TargetUserName");
-                let logon_type = event_data.get_value("LogonType");
-                let event_id_type= event.system.get_event_id_type().to_string();
-                if let Some(sid) = sid {
-                    if !WHITELISTED_SID.iter().any(|ignore_sid| {sid.starts_with(ignore_sid)}) {
-                        info!(action="logon_logoff_event", event_type=event_id_type, user_sid = sid, username = username, logon_type=logon_type);
-                        windows_api::collect_logs();
-                    }
-                }
-            },
-            None => warn!(action="logon_logoff_event", "No Event Data for Event ID: {}", event.system.event_id)
-        };
-    });
-    let logon_loggoff_event_watcher = windows_api::event_watcher::register_event_watcher(&LOGON_LOGOFF_EVENT_XPATH, Some(Duration::from_millis(100)),logon_logoff_event_callback, );
-
-    match logon_loggoff_event_watcher {
-        Ok(_) => info!(action="logon_watcher_start", "Logon watcher started sucessfully"),
-        Err(e) => error!(action="logon_watcher_start", "Error starting logon watcher - {}", e)
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [command, path] if command == "replay" => replay::run(Path::new(path), &load_replay_config(Config::load())),
+        [command, path, flag, config_path] if command == "replay" && flag == "--config" => {
+            replay::run(Path::new(path), &load_replay_config(Config::load_from(Path::new(config_path))))
+        },
+        [command, args @ ..] if command == "sessions" => sessions::run(args, &Config::load().config.log_dir()),
+        _ => start_service_dispatcher()
     };
 
-    tokio::runtime::Builder::new_multi_thread()
-    .enable_all()
-    .build()
-    .unwrap()
-    .block_on(async {
-        let shutdown = tokio::spawn(async move {
-            let mut interval = interval(Duration::from_millis(100));
-            loop {
-                interval.tick().await;
-                if shutdown_rx.try_recv().is_ok() {
-                    info!(action="service_shutdown", "Shutdown signal received");
-                    break;
-                }
-            }
-        });
-
-        tokio::select! {
-            _ = shutdown => {}
-        };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    });
+fn load_replay_config(loaded_config: LoadedConfig) -> LogonLogoffConfig {
+    for err in &loaded_config.errors {
+        eprintln!("{}", err);
+    }
+    loaded_config.config.logon_logoff
 }
 
-fn main() -> Result<()> {
+#[cfg(windows)]
+fn start_service_dispatcher() -> platform::Result<()> {
     service_dispatcher::start(service_helpers::config::SERVICE_NAME, ffi_service_main)?;
     Ok(())
 }
+
+#[cfg(not(windows))]
+fn start_service_dispatcher() -> platform::Result<()> {
+    Err("The cua service only runs on Windows, usage: cua replay <file> [--config <cua.toml>] or cua sessions [--from <time>] [--to <time>]".into())
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::events::Event;
+use crate::events::debounce::DebouncedCallback;
+
+use super::{AuditPolicyProvider, AuditSetting, CurrentUserInfo, EntraJoinInfo, EventCallback, EventSource, JoinInfoProvider, Platform, Result, SessionInfo, SessionProvider, Subscription, WatcherHandle};
+
+struct FakeSubscription {
+    id: usize,
+    subscription: Subscription,
+    callback: DebouncedCallback,
+}
+
+/// Event source driven by `emit`. The XPath query is recorded but not evaluated,
+/// every emitted event goes to every subscription.
+#[derive(Clone, Default)]
+pub struct FakeEventSource {
+    subscriptions: Arc<Mutex<Vec<FakeSubscription>>>,
+    next_id: Arc<Mutex<usize>>,
+}
+
+/// Removes its subscription from the source when dropped, delivering the events its
+/// debouncer held back like the Windows watcher does.
+struct FakeWatcherHandle {
+    id: usize,
+    subscriptions: Arc<Mutex<Vec<FakeSubscription>>>,
+}
+
+impl Drop for FakeWatcherHandle {
+    fn drop(&mut self) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let removed = subscriptions.iter().position(|subscription| subscription.id == self.id).map(|position| subscriptions.remove(position));
+        drop(subscriptions);
+        drop(removed);
+    }
+}
+
+impl FakeEventSource {
+    pub fn emit(&self, event: Event) {
+        for subscription in self.subscriptions.lock().unwrap().iter() {
+            subscription.callback.push(event.clone());
+        }
+    }
+
+    /// Delivers the events trailing debounce strategies are holding back.
+    pub fn flush(&self) {
+        for subscription in self.subscriptions.lock().unwrap().iter() {
+            subscription.callback.flush_all();
+        }
+    }
+
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.lock().unwrap().iter().map(|s| s.subscription.clone()).collect()
+    }
+}
+
+impl EventSource for FakeEventSource {
+    fn subscribe(&self, subscription: Subscription, callback: EventCallback) -> Result<WatcherHandle> {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        let callback = DebouncedCallback::new(&subscription.name, subscription.debounce, subscription.debounce_strategy, callback)?;
+        self.subscriptions.lock().unwrap().push(FakeSubscription { id, subscription, callback });
+        Ok(Box::new(FakeWatcherHandle { id, subscriptions: self.subscriptions.clone() }))
+    }
+}
+
+#[derive(Default)]
+pub struct FakeSessionProvider {
+    pub user: Mutex<Option<CurrentUserInfo>>,
+    pub sessions: Mutex<Vec<SessionInfo>>,
+}
+
+impl SessionProvider for FakeSessionProvider {
+    fn get_user_info(&self) -> Result<Option<CurrentUserInfo>> {
+        Ok(self.user.lock().unwrap().clone())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionInfo>> {
+        Ok(self.sessions.lock().unwrap().clone())
+    }
+}
+
+#[derive(Default)]
+pub struct FakeJoinInfoProvider {
+    pub tenants: Mutex<Vec<EntraJoinInfo>>,
+}
+
+impl JoinInfoProvider for FakeJoinInfoProvider {
+    fn get_entra_join_info(&self) -> Result<Vec<EntraJoinInfo>> {
+        Ok(self.tenants.lock().unwrap().clone())
+    }
+}
+
+/// Audits everything unless a subcategory's setting is in `settings`.
+#[derive(Default)]
+pub struct FakeAuditPolicyProvider {
+    pub settings: Mutex<HashMap<String, AuditSetting>>,
+}
+
+impl AuditPolicyProvider for FakeAuditPolicyProvider {
+    fn query_audit_policy(&self, subcategory_guids: &[&str]) -> Result<Vec<AuditSetting>> {
+        let settings = self.settings.lock().unwrap();
+        Ok(subcategory_guids
+            .iter()
+            .map(|guid| settings.get(*guid).copied().unwrap_or(AuditSetting { success: true, failure: true }))
+            .collect())
+    }
+}
+
+/// A `Platform` built from fakes, with handles kept so tests can drive them. State is kept
+/// in a temporary directory removed when it is dropped.
+pub struct FakePlatform {
+    pub events: FakeEventSource,
+    pub sessions: Arc<FakeSessionProvider>,
+    pub join_info: Arc<FakeJoinInfoProvider>,
+    pub audit_policy: Arc<FakeAuditPolicyProvider>,
+    pub state_dir: tempfile::TempDir,
+}
+
+impl Default for FakePlatform {
+    fn default() -> Self {
+        Self {
+            events: FakeEventSource::default(),
+            sessions: Arc::default(),
+            join_info: Arc::default(),
+            audit_policy: Arc::default(),
+            state_dir: tempfile::tempdir().unwrap(),
+        }
+    }
+}
+
+impl FakePlatform {
+    pub fn platform(&self) -> Platform {
+        Platform {
+            events: Box::new(self.events.clone()),
+            sessions: self.sessions.clone(),
+            join_info: self.join_info.clone(),
+            audit_policy: self.audit_policy.clone(),
+            state_dir: self.state_dir.path().to_path_buf(),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Traits the service core is written against. The Windows implementations live in
+//! `windows_api`, the in-memory ones in `fake` so the core can be tested anywhere.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::events::Event;
+use crate::events::debounce::DebounceStrategy;
+use crate::events::query::QueryList;
+
+#[cfg(test)]
+pub mod fake;
+pub mod utf16;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub type EventCallback = Box<dyn FnMut(Event) + Send>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntraJoinInfo {
+    pub tenant_id: String,
+    pub device_id: String,
+    pub registered_user: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurrentUserInfo {
+    pub sid: String,
+    pub username: String,
+    pub user_type: String,
+    pub azure_ad_object_id: Option<String>,
+}
+
+/// WTS connect state of a session.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum ConnectState {
+    Active,
+    Connected,
+    ConnectQuery,
+    Shadow,
+    Disconnected,
+    Idle,
+    Listen,
+    Reset,
+    Down,
+    Init,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub session_id: u32,
+    /// e.g. `Console`, `RDP-Tcp#3` or `Services`.
+    pub station_name: String,
+    pub connect_state: ConnectState,
+    /// The remote client's machine name and IP address, for remote sessions.
+    pub client_name: Option<String>,
+    pub client_address: Option<String>,
+    /// None for sessions nobody is logged into, such as the services session and RDP listeners.
+    pub user: Option<CurrentUserInfo>,
+}
+
+/// Session notifications the service control manager sends the service.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum SessionChangeType {
+    Logon,
+    Logoff,
+    Lock,
+    Unlock,
+    RemoteConnect,
+    RemoteDisconnect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionChange {
+    pub change_type: SessionChangeType,
+    pub session_id: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    /// Identifies the watcher's bookmark between restarts.
+    pub name: String,
+    /// The channels and XPath queries to read events from.
+    pub query: QueryList,
+    pub debounce: Option<Duration>,
+    pub debounce_strategy: DebounceStrategy,
+    /// How far back to read when there is no bookmark to resume from.
+    pub look_back: Duration,
+}
+
+/// Whether successes and failures in an audit policy subcategory are audited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AuditSetting {
+    pub success: bool,
+    pub failure: bool,
+}
+
+/// Keeps a subscription running. Dropping it stops the subscription, waits for a callback in
+/// progress to return, passes the events its debouncer held back to the callback and releases
+/// everything the subscription held.
+pub type WatcherHandle = Box<dyn Send>;
+
+/// Delivers events matching the subscription's query to a callback.
+pub trait EventSource {
+    fn subscribe(&self, subscription: Subscription, callback: EventCallback) -> Result<WatcherHandle>;
+}
+
+/// Looks up who is logged into the device.
+pub trait SessionProvider: Send + Sync {
+    /// The user logged into the active console session.
+    fn get_user_info(&self) -> Result<Option<CurrentUserInfo>>;
+    /// Every session on the device, console and remote.
+    fn list_sessions(&self) -> Result<Vec<SessionInfo>>;
+}
+
+/// Reads the Entra ID (Azure AD) tenants the device is joined to.
+pub trait JoinInfoProvider: Send + Sync {
+    fn get_entra_join_info(&self) -> Result<Vec<EntraJoinInfo>>;
+}
+
+/// Reads the effective system audit policy.
+pub trait AuditPolicyProvider: Send + Sync {
+    /// The setting for each subcategory GUID, in the same order.
+    fn query_audit_policy(&self, subcategory_guids: &[&str]) -> Result<Vec<AuditSetting>>;
+}
+
+pub struct Platform {
+    pub events: Box<dyn EventSource>,
+    pub sessions: Arc<dyn SessionProvider>,
+    pub join_info: Arc<dyn JoinInfoProvider>,
+    pub audit_policy: Arc<dyn AuditPolicyProvider>,
+    /// Where state kept between restarts is stored.
+    pub state_dir: PathBuf,
+}
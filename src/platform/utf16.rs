@@ -0,0 +1,33 @@
+//! Converting the UTF-16 buffers Windows APIs fill into Rust strings.
+
+/// The text before the first NUL, or the whole buffer when there is none. Anything after the
+/// NUL is left over from an earlier, longer value. Invalid UTF-16 is replaced rather than
+/// failing the whole string.
+pub fn to_string(buffer: &[u16]) -> String {
+    let len = buffer.iter().position(|&unit| unit == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str) -> Vec<u16> {
+        text.encode_utf16().collect()
+    }
+
+    #[test]
+    fn stops_at_first_nul() {
+        assert_eq!(to_string(&utf16("<Event/>\0")), "<Event/>");
+        assert_eq!(to_string(&utf16("<Event/>\0ent><System/>\0\0")), "<Event/>");
+        assert_eq!(to_string(&utf16("no terminator")), "no terminator");
+        assert_eq!(to_string(&utf16("\0<Event/>")), "");
+        assert_eq!(to_string(&[]), "");
+    }
+
+    #[test]
+    fn keeps_non_ascii_and_replaces_invalid_units() {
+        assert_eq!(to_string(&utf16("Jördan Gömes 🙂\0")), "Jördan Gömes 🙂");
+        assert_eq!(to_string(&[0x4a, 0xd800, 0x6f, 0]), "J\u{fffd}o");
+    }
+}
@@ -0,0 +1,588 @@
+//! The records cua writes and the schema downstream parsers can rely on. Every record is
+//! emitted through `emit` as one JSON line with `action` and `schema_version` ahead of its
+//! own fields. The JSON Schema for each is generated by the tests and checked in under
+//! `schemas/`.
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::events::{Event, EventIdType, System};
+use crate::events::audit_policy::AuditOutcome;
+use crate::events::correlation::Correlation;
+use crate::events::integrity::{self, IntegrityEventType};
+use crate::events::logon::LogonEvent;
+use crate::events::queue::Watcher;
+use crate::logging::RECORD_FIELD;
+use crate::platform::{ConnectState, CurrentUserInfo, EntraJoinInfo, SessionChange, SessionChangeType, SessionInfo};
+use crate::tenants::TenantDiff;
+
+/// Bumped whenever a field is removed or changes meaning, adding optional fields doesn't.
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub trait Record: Serialize + JsonSchema {
+    const ACTION: &'static str;
+}
+
+pub fn emit<R: Record>(record: &R) {
+    match serde_json::to_string(record) {
+        Ok(json) => info!(action = R::ACTION, schema_version = SCHEMA_VERSION, { RECORD_FIELD } = json),
+        Err(err) => error!(action = R::ACTION, "Unable to serialize record - {}", err)
+    }
+}
+
+/// An interactive logon or logoff from the Security log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LogonLogoffRecord {
+    pub event_type: EventIdType,
+    /// When Windows raised the event, `timestamp` is when cua recorded it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_time: Option<DateTime<Utc>>,
+    /// `EventRecordID`, unique within the channel, so duplicates can be spotted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_id: Option<u64>,
+    pub user_sid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// `LogonType` from 4624, or from the matching logon for logoffs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_type: Option<String>,
+    /// `TargetLogonId`, e.g. `0x4a0b2c`, the same for a logon and its logoff.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_id: Option<String>,
+    /// The logon ID of the other half of a split admin token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linked_logon_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_process: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authentication_package: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elevated_token: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virtual_account: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workstation_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_guid: Option<String>,
+    /// Logoffs only, whether the logon with the same `logon_id` was seen.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_matched: Option<bool>,
+    /// When the matching logon happened and how long the session lasted, for logoffs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_time: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<i64>,
+}
+
+impl LogonLogoffRecord {
+    pub fn new(event_type: EventIdType, logon: LogonEvent) -> LogonLogoffRecord {
+        LogonLogoffRecord {
+            event_type,
+            event_time: None,
+            record_id: None,
+            user_sid: logon.target_user_sid,
+            username: logon.target_user_name,
+            logon_type: logon.logon_type.map(|logon_type| logon_type.to_string()),
+            logon_id: logon.target_logon_id.map(|logon_id| format!("{:#x}", logon_id)),
+            linked_logon_id: logon.target_linked_logon_id.map(|logon_id| format!("{:#x}", logon_id)),
+            logon_process: logon.logon_process_name,
+            authentication_package: logon.authentication_package_name,
+            elevated_token: logon.elevated_token,
+            virtual_account: logon.virtual_account,
+            ip_address: logon.ip_address,
+            ip_port: logon.ip_port,
+            workstation_name: logon.workstation_name,
+            logon_guid: logon.logon_guid,
+            logon_matched: None,
+            logon_time: None,
+            duration_secs: None,
+        }
+    }
+
+    /// Adds when Windows raised the event and its record ID.
+    pub fn with_system(mut self, system: &System) -> Self {
+        self.event_time = system.time_created();
+        self.record_id = system.event_record_id;
+        self
+    }
+
+    /// Adds what is known about the logon a logoff at `time` ends.
+    pub fn with_correlation(mut self, time: DateTime<Utc>, correlation: Correlation) -> Self {
+        match correlation {
+            Correlation::Matched(open) => {
+                self.logon_matched = Some(true);
+                self.logon_type = self.logon_type.or(open.logon_type.map(|logon_type| logon_type.to_string()));
+                self.duration_secs = Some((time - open.time).num_seconds());
+                self.logon_time = Some(open.time);
+            },
+            Correlation::Unmatched => self.logon_matched = Some(false),
+            Correlation::Opened | Correlation::Ignored => {}
+        }
+        self
+    }
+}
+
+impl Record for LogonLogoffRecord {
+    const ACTION: &'static str = "logon_logoff_event";
+}
+
+/// A failed logon at the console, over RDP, when unlocking or with cached credentials.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct FailedLogonRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_type: Option<String>,
+    /// NTSTATUS codes, e.g. `0xC000006D`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub_status: Option<String>,
+    /// `sub_status`, or `status` when it isn't set, as text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workstation_name: Option<String>,
+}
+
+impl Record for FailedLogonRecord {
+    const ACTION: &'static str = "failed_logon_event";
+}
+
+/// The Security log being cleared, the event log service stopping or auditing being changed,
+/// after which missing logons don't mean nobody used the device.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct AuditIntegrityRecord {
+    pub event_type: IntegrityEventType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_time: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// Who cleared the log or changed the policy, not logged for a service shutdown.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_sid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_id: Option<String>,
+    /// Audit policy changes only, e.g. `Logon/Logoff`, `Logon` and `Success removed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subcategory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subcategory_guid: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+    /// Object auditing changes only, the object and the process that changed it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_name: Option<String>,
+}
+
+impl AuditIntegrityRecord {
+    /// None for events that aren't one of `integrity::INTEGRITY_EVENT_IDS`.
+    pub fn from_event(event: &Event) -> Option<AuditIntegrityRecord> {
+        let event_type = IntegrityEventType::from_event_id(event.system.event_id)?;
+        let value = |name: &str| event.get_present_value(name);
+        Some(AuditIntegrityRecord {
+            event_type,
+            event_time: event.system.time_created(),
+            record_id: event.system.event_record_id,
+            channel: event.system.channel.clone(),
+            user_sid: value("SubjectUserSid"),
+            username: value("SubjectUserName"),
+            domain: value("SubjectDomainName"),
+            logon_id: value("SubjectLogonId"),
+            category: value("CategoryId").map(integrity::decode_audit_message),
+            subcategory: value("SubcategoryId").map(integrity::decode_audit_message),
+            subcategory_guid: value("SubcategoryGuid"),
+            changes: value("AuditPolicyChanges").map(|changes| integrity::audit_policy_changes(&changes)).unwrap_or_default(),
+            object_type: value("ObjectType"),
+            object_name: value("ObjectName"),
+            process_name: value("ProcessName"),
+        })
+    }
+}
+
+impl Record for AuditIntegrityRecord {
+    const ACTION: &'static str = "audit_integrity_event";
+}
+
+/// The audit policy settings cua relies on, checked at startup and every
+/// `check_interval_secs` after.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct AuditPolicyStatusRecord {
+    pub subcategories: Vec<AuditSubcategoryRecord>,
+    /// Settings an enabled watcher needs that are off, so its events won't be logged.
+    pub missing: Vec<MissingAuditSettingRecord>,
+}
+
+impl Record for AuditPolicyStatusRecord {
+    const ACTION: &'static str = "audit_policy_status";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct AuditSubcategoryRecord {
+    pub subcategory: String,
+    pub guid: String,
+    pub success: bool,
+    pub failure: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct MissingAuditSettingRecord {
+    pub subcategory: String,
+    pub outcome: AuditOutcome,
+    pub event_ids: Vec<u32>,
+    pub watcher: Watcher,
+}
+
+/// A workstation lock or unlock, or the screensaver starting or stopping.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct PresenceRecord {
+    pub event_type: EventIdType,
+    pub user_sid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<u32>,
+}
+
+impl Record for PresenceRecord {
+    const ACTION: &'static str = "presence_event";
+}
+
+/// A session notification from the service control manager, raised even when Security
+/// auditing is off.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct SessionChangeRecord {
+    pub event_type: SessionChangeType,
+    pub session_id: u32,
+}
+
+impl Record for SessionChangeRecord {
+    const ACTION: &'static str = "session_change_event";
+}
+
+impl From<SessionChange> for SessionChangeRecord {
+    fn from(change: SessionChange) -> Self {
+        Self { event_type: change.change_type, session_id: change.session_id }
+    }
+}
+
+/// An Entra ID tenant the device is joined to.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct TenantInfoRecord {
+    pub tenant_id: String,
+    pub device_id: String,
+    pub registered_user: String,
+}
+
+impl Record for TenantInfoRecord {
+    const ACTION: &'static str = "tenant_info";
+}
+
+impl From<EntraJoinInfo> for TenantInfoRecord {
+    fn from(join_info: EntraJoinInfo) -> Self {
+        Self {
+            tenant_id: join_info.tenant_id,
+            device_id: join_info.device_id,
+            registered_user: join_info.registered_user,
+        }
+    }
+}
+
+/// The tenants the device is joined to changed since they were last read, written ahead of
+/// the `tenant_info` records for the new set.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct TenantJoinChangedRecord {
+    pub joined: Vec<TenantInfoRecord>,
+    pub left: Vec<TenantInfoRecord>,
+    /// Joined to the same tenant again, usually with a new device ID.
+    pub rejoined: Vec<TenantRejoinedRecord>,
+}
+
+impl Record for TenantJoinChangedRecord {
+    const ACTION: &'static str = "tenant_join_changed";
+}
+
+impl From<TenantDiff> for TenantJoinChangedRecord {
+    fn from(diff: TenantDiff) -> Self {
+        Self {
+            joined: diff.joined.into_iter().map(Into::into).collect(),
+            left: diff.left.into_iter().map(Into::into).collect(),
+            rejoined: diff.rejoined
+                .into_iter()
+                .map(|(before, after)| TenantRejoinedRecord {
+                    tenant_id: after.tenant_id,
+                    device_id: after.device_id,
+                    registered_user: after.registered_user,
+                    previous_device_id: before.device_id,
+                    previous_registered_user: before.registered_user,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct TenantRejoinedRecord {
+    pub tenant_id: String,
+    pub device_id: String,
+    pub registered_user: String,
+    pub previous_device_id: String,
+    pub previous_registered_user: String,
+}
+
+/// The user logged into the active console session, if there is one.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct CurrentUserRecord {
+    pub logged_in: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_sid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// `AzureAD` or `DomainOrLocal`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_type: Option<String>,
+    /// Only for Azure AD users.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub azure_ad_object_id: Option<String>,
+}
+
+impl Record for CurrentUserRecord {
+    const ACTION: &'static str = "current_user_info";
+}
+
+impl From<Option<CurrentUserInfo>> for CurrentUserRecord {
+    fn from(user_info: Option<CurrentUserInfo>) -> Self {
+        match user_info {
+            Some(user_info) => Self {
+                logged_in: true,
+                user_sid: Some(user_info.sid),
+                username: Some(user_info.username),
+                user_type: Some(user_info.user_type),
+                azure_ad_object_id: user_info.azure_ad_object_id,
+            },
+            None => Self { logged_in: false, user_sid: None, username: None, user_type: None, azure_ad_object_id: None }
+        }
+    }
+}
+
+/// Written every `interval_secs` so a quiet log can be told apart from a dead agent.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct HeartbeatRecord {
+    pub agent_version: String,
+    pub uptime_secs: u64,
+    /// Events taken off the queue since the service started, including duplicates that weren't recorded.
+    pub events_processed: u64,
+    /// Events dropped because the queue was full since the service started.
+    pub events_dropped: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_event_time: Option<DateTime<Utc>>,
+    /// Missing if the console session couldn't be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub console_user: Option<CurrentUserRecord>,
+}
+
+impl Record for HeartbeatRecord {
+    const ACTION: &'static str = "heartbeat";
+}
+
+/// Every session on the device, console and remote, alongside `current_user_info`.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct CurrentSessionsRecord {
+    pub sessions: Vec<SessionEntry>,
+}
+
+impl Record for CurrentSessionsRecord {
+    const ACTION: &'static str = "current_sessions_info";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct SessionEntry {
+    pub session_id: u32,
+    pub station_name: String,
+    pub connect_state: ConnectState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_sid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub azure_ad_object_id: Option<String>,
+}
+
+impl From<SessionInfo> for SessionEntry {
+    fn from(session: SessionInfo) -> Self {
+        let user = session.user;
+        Self {
+            session_id: session.session_id,
+            station_name: session.station_name,
+            connect_state: session.connect_state,
+            client_name: session.client_name,
+            client_address: session.client_address,
+            user_sid: user.as_ref().map(|user| user.sid.clone()),
+            username: user.as_ref().map(|user| user.username.clone()),
+            user_type: user.as_ref().map(|user| user.user_type.clone()),
+            azure_ad_object_id: user.and_then(|user| user.azure_ad_object_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::SessionRecord;
+    use crate::test_support::capture_records;
+    use serde_json::{Map, Value, json};
+    use std::path::PathBuf;
+
+    /// JSON Schema for a whole log line holding the record, including the fields added by
+    /// `emit` and the log format.
+    fn json_schema<R: Record>() -> Value {
+        let mut schema = schemars::schema_for!(R).to_value();
+        let record_properties = schema.get("properties").and_then(Value::as_object).cloned().unwrap_or_default();
+        let record_required = schema.get("required").and_then(Value::as_array).cloned().unwrap_or_default();
+
+        let mut properties = Map::new();
+        properties.insert("timestamp".to_string(), json!({ "type": "string", "format": "date-time" }));
+        properties.insert("level".to_string(), json!({ "type": "string" }));
+        properties.insert("action".to_string(), json!({ "const": R::ACTION }));
+        properties.insert("schema_version".to_string(), json!({ "const": SCHEMA_VERSION }));
+        properties.extend(record_properties);
+        properties.insert("target".to_string(), json!({ "type": "string" }));
+
+        let mut required: Vec<Value> = ["timestamp", "level", "action", "schema_version"].into_iter().map(Value::from).collect();
+        required.extend(record_required);
+        required.push("target".into());
+
+        schema["properties"] = Value::Object(properties);
+        schema["required"] = Value::Array(required);
+
+        // Keep the header keys at the top of the checked in files
+        let mut ordered = Map::new();
+        if let Value::Object(mut schema) = schema {
+            for key in ["$schema", "title", "description", "type", "properties", "required"] {
+                if let Some(value) = schema.remove(key) {
+                    ordered.insert(key.to_string(), value);
+                }
+            }
+            ordered.extend(schema);
+        }
+        Value::Object(ordered)
+    }
+
+    /// Every record type's action and schema, for generating the files under `schemas/`.
+    fn json_schemas() -> Vec<(&'static str, Value)> {
+        vec![
+            (LogonLogoffRecord::ACTION, json_schema::<LogonLogoffRecord>()),
+            (FailedLogonRecord::ACTION, json_schema::<FailedLogonRecord>()),
+            (PresenceRecord::ACTION, json_schema::<PresenceRecord>()),
+            (AuditIntegrityRecord::ACTION, json_schema::<AuditIntegrityRecord>()),
+            (AuditPolicyStatusRecord::ACTION, json_schema::<AuditPolicyStatusRecord>()),
+            (SessionChangeRecord::ACTION, json_schema::<SessionChangeRecord>()),
+            (TenantInfoRecord::ACTION, json_schema::<TenantInfoRecord>()),
+            (TenantJoinChangedRecord::ACTION, json_schema::<TenantJoinChangedRecord>()),
+            (CurrentUserRecord::ACTION, json_schema::<CurrentUserRecord>()),
+            (CurrentSessionsRecord::ACTION, json_schema::<CurrentSessionsRecord>()),
+            (HeartbeatRecord::ACTION, json_schema::<HeartbeatRecord>()),
+            (SessionRecord::ACTION, json_schema::<SessionRecord>()),
+        ]
+    }
+
+    fn schema_path(action: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("schemas").join(format!("{}.schema.json", action))
+    }
+
+    /// Run with `CUA_UPDATE_SCHEMAS=1` to regenerate the files after changing a record.
+    #[test]
+    fn checked_in_schemas_are_current() {
+        for (action, schema) in json_schemas() {
+            let path = schema_path(action);
+            let generated = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+            if std::env::var_os("CUA_UPDATE_SCHEMAS").is_some() {
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, &generated).unwrap();
+            }
+            let checked_in = std::fs::read_to_string(&path).unwrap_or_default();
+            assert_eq!(checked_in, generated, "{} is out of date, rerun with CUA_UPDATE_SCHEMAS=1", path.display());
+        }
+    }
+
+    fn assert_matches_schema<R: Record>(line: &Value) {
+        let schema = json_schema::<R>();
+        let properties = schema["properties"].as_object().unwrap();
+        for field in line.as_object().unwrap().keys() {
+            assert!(properties.contains_key(field), "{} is not in the {} schema", field, R::ACTION);
+        }
+        for field in schema["required"].as_array().unwrap() {
+            assert!(line.get(field.as_str().unwrap()).is_some(), "{} is missing from {}", field, line);
+        }
+        assert_eq!(line["action"], R::ACTION);
+        assert_eq!(line["schema_version"], SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn emitted_lines_match_schema() {
+        let logon = LogonLogoffRecord::new(EventIdType::Logon, LogonEvent {
+            target_user_sid: "S-1-5-21-1".to_string(),
+            target_user_name: Some("Jordan".to_string()),
+            logon_type: Some(2),
+            target_logon_id: Some(0x4a0b2c),
+            authentication_package_name: Some("Negotiate".to_string()),
+            elevated_token: Some(true),
+            ip_port: Some(3389),
+            ..LogonEvent::default()
+        }).with_system(&System { event_id: 4624, event_record_id: Some(48211), ..System::default() });
+        let logoff = LogonLogoffRecord::new(EventIdType::LogoffInteractive, LogonEvent { target_user_sid: "S-1-5-21-1".to_string(), ..LogonEvent::default() });
+        let tenant = TenantInfoRecord { tenant_id: "338f14b1-cb11-41e8-90ae-b06bc0fdd75a".to_string(), device_id: "af4edcd4-4bb6-4679-8b0e-64c3262a1de4".to_string(), registered_user: "test@jordangomes.com".to_string() };
+        let no_user = CurrentUserRecord::from(None);
+
+        let lines = capture_records(|| {
+            emit(&logon);
+            emit(&logoff);
+            emit(&tenant);
+            emit(&no_user);
+        });
+
+        assert_matches_schema::<LogonLogoffRecord>(&lines[0]);
+        assert_matches_schema::<LogonLogoffRecord>(&lines[1]);
+        assert_matches_schema::<TenantInfoRecord>(&lines[2]);
+        assert_matches_schema::<CurrentUserRecord>(&lines[3]);
+        assert_eq!(lines[0]["event_type"], "Logon");
+        assert_eq!(lines[0]["logon_id"], "0x4a0b2c");
+        assert_eq!(lines[0]["record_id"], 48211);
+        assert_eq!(lines[0]["elevated_token"], true);
+        assert_eq!(lines[0]["ip_port"], 3389);
+        assert_eq!(lines[1].get("logon_id"), None);
+        assert_eq!(lines[1].get("logon_type"), None);
+        assert_eq!(lines[3]["logged_in"], false);
+        assert_eq!(lines[0].as_object().unwrap().keys().collect::<Vec<_>>(), vec!["timestamp", "level", "action", "schema_version", "event_type", "record_id", "user_sid", "username", "logon_type", "logon_id", "authentication_package", "elevated_token", "ip_port", "target"]);
+    }
+}
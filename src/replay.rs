@@ -0,0 +1,150 @@
+//! `cua replay <file>` - runs exported Security events back through the logon pipeline and
+//! prints the records the service would have written. Accepts the XML `EvtRender` produces,
+//! one event after another, or a `wevtutil qe Security /f:xml` export with or without an
+//! `<Events>` root.
+//!
+//! Only `logon_logoff_event` records are replayed, `tenant_info` and `current_user_info`
+//! describe the device at the time they are collected so would be wrong for a capture.
+
+use std::path::Path;
+
+use quick_xml::events::Event as XmlEvent;
+use quick_xml::Reader;
+use tracing::{error, warn};
+
+use crate::events::Event;
+use crate::events::correlation::LogonTracker;
+use crate::events::debounce::Debouncer;
+use crate::logging::json_subscriber;
+use crate::platform;
+use crate::config::LogonLogoffConfig;
+use crate::service::{handle_logon_logoff_event, matches_logon_logoff_query};
+
+pub fn run(path: &Path, config: &LogonLogoffConfig) -> platform::Result<()> {
+    let xml = std::fs::read_to_string(path)?;
+    let event_xml = split_events(&xml)?;
+    tracing::subscriber::with_default(json_subscriber(std::io::stdout), || {
+        replay(parse_events(&event_xml), config);
+    });
+    Ok(())
+}
+
+/// Splits a document into the XML of each `<Event>` element it contains.
+pub fn split_events(xml: &str) -> Result<Vec<&str>, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    let mut events = Vec::new();
+    let mut event_start = None;
+    loop {
+        let position = reader.buffer_position() as usize;
+        match reader.read_event()? {
+            XmlEvent::Start(element) if element.local_name().as_ref() == b"Event" => event_start = Some(position),
+            XmlEvent::End(element) if element.local_name().as_ref() == b"Event" => {
+                if let Some(start) = event_start.take() {
+                    events.push(&xml[start..reader.buffer_position() as usize]);
+                }
+            },
+            XmlEvent::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+/// Deserializes each event, logging the ones that fail the same way the live watcher does.
+pub fn parse_events(event_xml: &[&str]) -> Vec<Event> {
+    event_xml
+        .iter()
+        .filter_map(|xml| match quick_xml::de::from_str::<Event>(xml) {
+            Ok(event) => Some(event),
+            Err(err) => {
+                error!(name="event_watcher", "Error serializing xml {:?}", err.to_string());
+                None
+            }
+        })
+        .collect()
+}
+
+/// Feeds events through the logon query filter, debounce and SID filter in the order Windows
+/// raised them, returning how many were recorded.
+pub fn replay(mut events: Vec<Event>, config: &LogonLogoffConfig) -> usize {
+    events.sort_by_key(|event| event.time_created());
+    if config.xpath.is_some() {
+        warn!(action="replay", "A custom xpath can't be evaluated offline, every event in the file is replayed");
+    }
+
+    let mut debouncer = Debouncer::new(config.watcher.debounce(), config.watcher.debounce_strategy);
+    let mut logons = LogonTracker::default();
+    let mut recorded = 0;
+    let mut handle = |event| if handle_logon_logoff_event(event, config, &mut logons) {
+        recorded += 1;
+    };
+    for event in events.into_iter().filter(|event| config.xpath.is_some() || matches_logon_logoff_query(event, config)) {
+        debouncer.push(event).into_iter().for_each(&mut handle);
+    }
+    debouncer.flush_all().into_iter().for_each(&mut handle);
+    recorded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{capture_records, records_with_action};
+
+    const USER_SID: &str = "S-1-12-1-2991438786-1147252871-734652841-3570430303";
+
+    fn logon_xml(time: &str, sid: &str, logon_type: &str) -> String {
+        format!("<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{{54849625-5478-4994-a5ba-3e3b0328c30d}}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='{time}'/><EventRecordID>48211</EventRecordID><Correlation/><Execution ProcessID='1008' ThreadID='1120'/><Channel>Security</Channel><Computer>jordans-desktop</Computer><Security/></System><EventData><Data Name='SubjectUserSid'>S-1-5-18</Data><Data Name='TargetUserSid'>{sid}</Data><Data Name='TargetUserName'>JordanGomes</Data><Data Name='TargetDomainName'>AzureAD</Data><Data Name='LogonType'>{logon_type}</Data><Data Name='IpAddress'>-</Data><Data Name='TargetOutboundDomainName'></Data></EventData></Event>")
+    }
+
+    fn logoff_xml(time: &str, sid: &str) -> String {
+        format!("<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{{54849625-5478-4994-a5ba-3e3b0328c30d}}'/><EventID>4647</EventID><TimeCreated SystemTime='{time}'/><EventRecordID>48230</EventRecordID><Channel>Security</Channel><Computer>jordans-desktop</Computer></System><EventData><Data Name='TargetUserSid'>{sid}</Data><Data Name='TargetUserName'>JordanGomes</Data><Data Name='TargetDomainName'>AzureAD</Data><Data Name='TargetLogonId'>0x4a0b2c</Data></EventData></Event>")
+    }
+
+    fn replay_xml(xml: &str) -> Vec<serde_json::Value> {
+        let event_xml = split_events(xml).unwrap();
+        capture_records(|| { replay(parse_events(&event_xml), &LogonLogoffConfig::default()); })
+    }
+
+    #[test]
+    fn splits_wevtutil_exports_with_and_without_root() {
+        let events = format!("{}\r\n{}", logon_xml("2026-02-27T05:22:35.4610070Z", USER_SID, "2"), logoff_xml("2026-02-27T09:00:00.0000000Z", USER_SID));
+        assert_eq!(split_events(&events).unwrap().len(), 2);
+        assert_eq!(split_events(&format!("<Events>{events}</Events>")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn replays_events_in_time_order() {
+        // wevtutil /rd:true exports newest first
+        let xml = format!("{}{}", logoff_xml("2026-02-27T09:00:00.0000000Z", USER_SID), logon_xml("2026-02-27T05:22:35.4610070Z", USER_SID, "2"));
+        let records = replay_xml(&xml);
+        let events = records_with_action(&records, "logon_logoff_event");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event_type"], "Logon");
+        assert_eq!(events[0]["event_time"], "2026-02-27T05:22:35.461007Z");
+        assert_eq!(events[0]["record_id"], 48211);
+        assert_eq!(events[1]["event_type"], "LogoffInteractive");
+        assert_eq!(events[1]["record_id"], 48230);
+    }
+
+    #[test]
+    fn applies_query_sid_filter_and_debounce() {
+        let xml = [
+            logon_xml("2026-02-27T05:22:35.0000000Z", USER_SID, "3"), // network logon, not in the query
+            logon_xml("2026-02-27T05:22:36.0000000Z", "S-1-5-90-0-1", "2"), // DWM
+            logon_xml("2026-02-27T05:22:40.0000000Z", USER_SID, "2"),
+            logon_xml("2026-02-27T05:22:40.0500000Z", USER_SID, "11"), // inside the debounce window
+            logon_xml("2026-02-27T05:22:40.2000000Z", USER_SID, "7"),
+        ].concat();
+        let records = replay_xml(&xml);
+        let events = records_with_action(&records, "logon_logoff_event");
+        assert_eq!(events.iter().map(|event| event["logon_type"].as_str().unwrap()).collect::<Vec<_>>(), vec!["2", "7"]);
+    }
+
+    #[test]
+    fn invalid_events_are_logged_and_skipped() {
+        let xml = format!("<Event><System><EventID>not a number</EventID></System></Event>{}", logon_xml("2026-02-27T05:22:35.4610070Z", USER_SID, "2"));
+        let records = replay_xml(&xml);
+        assert_eq!(records[0]["level"], "ERROR");
+        assert_eq!(records_with_action(&records, "logon_logoff_event").len(), 1);
+    }
+}
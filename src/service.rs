@@ -0,0 +1,1075 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use tokio::time::{Duration, Instant, interval, interval_at};
+use tracing::{debug, error, info, warn};
+
+use crate::config::{Config, FailedLogonConfig, LogonLogoffConfig};
+use crate::events::{Event, EventIdType, ntstatus};
+use crate::events::audit_policy::{self, AuditOutcome};
+use crate::events::correlation::LogonTracker;
+use crate::events::dedupe::Seen;
+use crate::events::integrity::IntegrityEventType;
+use crate::events::logon::{self, LogonEvent};
+use crate::events::queue::{self, EventQueue, QueuedEvent, Watcher};
+use crate::heartbeat::{AgentStats, Heartbeat, emit_heartbeat};
+use crate::logging::rotation::RotationErrors;
+use crate::records::{self, AuditIntegrityRecord, AuditPolicyStatusRecord, AuditSubcategoryRecord, MissingAuditSettingRecord, CurrentSessionsRecord, CurrentUserRecord, FailedLogonRecord, LogonLogoffRecord, PresenceRecord, SessionChangeRecord, TenantInfoRecord, TenantJoinChangedRecord};
+use crate::platform::{AuditPolicyProvider, EventCallback, JoinInfoProvider, Platform, SessionChange, SessionProvider, Subscription, WatcherHandle};
+use crate::state::bookmark::{BookmarkStore, bookmark_xml};
+use crate::state::logons::LogonStore;
+use crate::state::record_ids::{RecordIdStore, RecordIds};
+use crate::state::tenants::TenantStore;
+use crate::tenants::TenantCache;
+
+/// Messages from the service control handler to the service loop.
+#[derive(Debug)]
+pub enum ServiceMessage {
+    Shutdown,
+    SessionChange(SessionChange),
+}
+
+pub fn service_loop(platform: &Platform, config: &Config, rotation_errors: &RotationErrors, service_rx: Receiver<ServiceMessage>) {
+    let (queue, mut events) = queue::event_queue(config.event_queue.capacity);
+    let mut processor = EventProcessor::new(platform, config);
+    let mut watchers = register_watchers(platform, config, &queue);
+
+    tokio::runtime::Builder::new_multi_thread()
+    .enable_all()
+    .build()
+    .unwrap()
+    .block_on(async {
+        let mut interval = interval(Duration::from_millis(100));
+        // Checked once before waiting for events, then every check interval
+        let check_interval = config.audit_policy.check_interval();
+        let mut audit_policy_interval = interval_at(Instant::now() + check_interval, check_interval);
+        if config.audit_policy.enabled {
+            check_audit_policy(platform.audit_policy.as_ref(), config);
+        }
+        let mut heartbeat = Heartbeat::new(config.heartbeat.interval());
+        loop {
+            tokio::select! {
+                Some(event) = events.recv() => processor.process(event),
+                _ = audit_policy_interval.tick(), if config.audit_policy.enabled => check_audit_policy(platform.audit_policy.as_ref(), config),
+                uptime = heartbeat.tick(), if config.heartbeat.enabled => emit_heartbeat(uptime, &processor.stats, platform.sessions.as_ref()),
+                _ = interval.tick() => {
+                    for err in rotation_errors.take() {
+                        warn!(action="log_rotation", "{}", err);
+                    }
+                    // Before saving, so the bookmark doesn't move past what was dropped
+                    processor.events_dropped(queue.take_dropped());
+                    processor.save_state();
+                    while let Ok(message) = service_rx.try_recv() {
+                        match message {
+                            ServiceMessage::Shutdown => {
+                                info!(action="service_shutdown", "Shutdown signal received");
+                                // Stop the subscriptions first so nothing is queued after the drain,
+                                // dropping them queues the events their debouncers held back
+                                watchers.clear();
+                                // Record what was already delivered before stopping
+                                while let Ok(event) = events.try_recv() {
+                                    processor.process(event);
+                                }
+                                processor.events_dropped(queue.take_dropped());
+                                processor.save_state();
+                                return;
+                            },
+                            ServiceMessage::SessionChange(change) => processor.session_change(change)
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Records the audit policy settings the enabled watchers need, warning about any that are
+/// off. Without them Windows never logs the events, so there is nothing else to notice.
+pub fn check_audit_policy(audit_policy: &dyn AuditPolicyProvider, config: &Config) {
+    let guids = audit_policy::SUBCATEGORIES.iter().map(|(guid, _)| *guid).collect::<Vec<&str>>();
+    let settings = match audit_policy.query_audit_policy(&guids) {
+        Ok(settings) => settings,
+        Err(err) => {
+            error!(action="audit_policy_status", "Unable to query audit policy - {}", err);
+            return;
+        }
+    };
+    let subcategories = audit_policy::SUBCATEGORIES
+        .iter()
+        .zip(settings)
+        .map(|((guid, name), setting)| AuditSubcategoryRecord { subcategory: name.to_string(), guid: guid.to_string(), success: setting.success, failure: setting.failure })
+        .collect::<Vec<AuditSubcategoryRecord>>();
+
+    let missing = audit_policy::REQUIREMENTS
+        .iter()
+        .filter(|requirement| is_enabled(requirement.watcher, config))
+        .filter(|requirement| !subcategories.iter().any(|subcategory| subcategory.guid == requirement.subcategory_guid && match requirement.outcome {
+            AuditOutcome::Success => subcategory.success,
+            AuditOutcome::Failure => subcategory.failure,
+        }))
+        .map(|requirement| MissingAuditSettingRecord {
+            subcategory: audit_policy::subcategory_name(requirement.subcategory_guid).unwrap_or(requirement.subcategory_guid).to_string(),
+            outcome: requirement.outcome,
+            event_ids: requirement.event_ids.to_vec(),
+            watcher: requirement.watcher,
+        })
+        .collect::<Vec<MissingAuditSettingRecord>>();
+    for setting in &missing {
+        warn!(action="audit_policy_missing", "{:?} auditing for {} is off, events {:?} for the {:?} watcher won't be logged", setting.outcome, setting.subcategory, setting.event_ids, setting.watcher);
+    }
+    records::emit(&AuditPolicyStatusRecord { subcategories, missing });
+}
+
+fn is_enabled(watcher: Watcher, config: &Config) -> bool {
+    config.watchers().iter().any(|(enabled, ..)| *enabled == watcher)
+}
+
+/// Subscribes each enabled watcher, their callbacks only put events on `queue`. Each
+/// subscription runs until its handle is dropped.
+pub fn register_watchers(platform: &Platform, config: &Config, queue: &EventQueue) -> Vec<WatcherHandle> {
+    let mut watchers = Vec::new();
+    for (watcher, watcher_config, xpath) in config.watchers() {
+        let subscription = Subscription {
+            name: watcher.name().to_string(),
+            query: watcher_config.query(&xpath),
+            debounce: watcher_config.debounce(),
+            debounce_strategy: watcher_config.debounce_strategy,
+            look_back: watcher_config.look_back(),
+        };
+        match platform.events.subscribe(subscription, enqueue(queue, watcher)) {
+            Ok(handle) => {
+                watchers.push(handle);
+                info!(action="watcher_start", watcher=watcher.name(), "{} watcher started sucessfully", watcher.name())
+            },
+            Err(e) => error!(action="watcher_start", watcher=watcher.name(), "Error starting {} watcher - {}", watcher.name(), e)
+        };
+    }
+    watchers
+}
+
+fn enqueue(queue: &EventQueue, watcher: Watcher) -> EventCallback {
+    let queue = queue.clone();
+    Box::new(move |event: Event| {
+        queue.push(watcher, event);
+    })
+}
+
+/// Handles queued events on the service loop, with the state that has to outlive one event.
+pub struct EventProcessor {
+    sessions: Arc<dyn SessionProvider>,
+    join_info: Arc<dyn JoinInfoProvider>,
+    logon_logoff_config: LogonLogoffConfig,
+    failed_logon_config: FailedLogonConfig,
+    logon_store: LogonStore,
+    logons: LogonTracker,
+    /// Whether `logons` changed since it was last saved.
+    logons_changed: bool,
+    record_id_store: RecordIdStore,
+    record_ids: RecordIds,
+    /// Whether `record_ids` changed since it was last saved.
+    record_ids_changed: bool,
+    /// The record IDs last written to `record_id_store`.
+    saved_record_ids: RecordIds,
+    /// Watchers that dropped events, their saved record IDs and bookmark stay where they
+    /// were so the dropped events are read again after a restart.
+    dropped_watchers: HashSet<Watcher>,
+    /// Watchers whose bookmark is behind the events processed since it was last saved.
+    bookmarks_changed: HashSet<Watcher>,
+    state_dir: PathBuf,
+    tenant_store: TenantStore,
+    tenants: TenantCache,
+    /// Whether `tenants` changed since they were last saved.
+    tenants_changed: bool,
+    /// Reported in the heartbeat.
+    pub stats: AgentStats,
+}
+
+impl EventProcessor {
+    pub fn new(platform: &Platform, config: &Config) -> Self {
+        let logon_store = LogonStore::new(&platform.state_dir);
+        let record_id_store = RecordIdStore::new(&platform.state_dir);
+        let tenant_store = TenantStore::new(&platform.state_dir);
+        Self {
+            sessions: platform.sessions.clone(),
+            join_info: platform.join_info.clone(),
+            logon_logoff_config: config.logon_logoff.clone(),
+            failed_logon_config: config.failed_logon.clone(),
+            logons: logon_store.load(),
+            logon_store,
+            logons_changed: false,
+            record_ids: record_id_store.load(),
+            saved_record_ids: record_id_store.load(),
+            record_id_store,
+            record_ids_changed: false,
+            dropped_watchers: HashSet::new(),
+            bookmarks_changed: HashSet::new(),
+            state_dir: platform.state_dir.clone(),
+            tenants: TenantCache::new(config.tenant_info.refresh_interval()).with_last(tenant_store.load()),
+            tenant_store,
+            tenants_changed: false,
+            stats: AgentStats::default(),
+        }
+    }
+
+    /// Records a session notification and collects the device state, the same as for a logon
+    /// event from the Security log.
+    pub fn session_change(&mut self, change: SessionChange) {
+        records::emit(&SessionChangeRecord::from(change));
+        self.collect_logs();
+    }
+
+    fn collect_logs(&mut self) {
+        collect_logs(self.sessions.as_ref(), self.join_info.as_ref(), &mut self.tenants);
+        self.tenants_changed |= self.tenants.take_changed();
+    }
+
+    /// Writes the state that changed since the last call. The service loop calls this on its
+    /// tick and at shutdown rather than after every event, so a burst of events or a
+    /// look-back replay costs one write. Bookmarks only move past events that have been
+    /// processed, so anything still queued is read again after a restart.
+    pub fn save_state(&mut self) {
+        if self.logons_changed {
+            match self.logon_store.save(&self.logons) {
+                Ok(()) => self.logons_changed = false,
+                Err(err) => warn!(action="logon_state_save", "Unable to save logon state to {} - {}", self.logon_store.path().display(), err)
+            }
+        }
+        if self.record_ids_changed {
+            let mut record_ids = self.record_ids.clone();
+            for watcher in &self.dropped_watchers {
+                match self.saved_record_ids.get(watcher) {
+                    Some(saved) => record_ids.insert(*watcher, saved.clone()),
+                    None => record_ids.remove(watcher)
+                };
+            }
+            match self.record_id_store.save(&record_ids) {
+                Ok(()) => {
+                    self.record_ids_changed = false;
+                    self.saved_record_ids = record_ids;
+                },
+                Err(err) => warn!(action="record_id_state_save", "Unable to save record IDs to {} - {}", self.record_id_store.path().display(), err)
+            }
+        }
+        if self.tenants_changed
+            && let Some(tenants) = self.tenants.last() {
+            match self.tenant_store.save(tenants) {
+                Ok(()) => self.tenants_changed = false,
+                Err(err) => warn!(action="tenant_state_save", "Unable to save tenants to {} - {}", self.tenant_store.path().display(), err)
+            }
+        }
+        let record_ids = &self.record_ids;
+        let state_dir = &self.state_dir;
+        let dropped_watchers = &self.dropped_watchers;
+        self.bookmarks_changed.retain(|watcher| {
+            if dropped_watchers.contains(watcher) {
+                return false;
+            }
+            let Some(bookmark_xml) = record_ids.get(watcher).and_then(bookmark_xml) else {
+                return false;
+            };
+            match BookmarkStore::new(state_dir, watcher.name()).save(&bookmark_xml) {
+                Ok(()) => false,
+                Err(err) => {
+                    warn!(action="bookmark_save", "Unable to save bookmark for {} - {}", watcher.name(), err);
+                    true
+                }
+            }
+        });
+    }
+
+    /// Counts the events the queue dropped. The watchers that dropped them stop moving their
+    /// bookmark, so a restart reads the dropped events again rather than skipping them.
+    pub fn events_dropped(&mut self, dropped: HashMap<Watcher, u64>) {
+        for (watcher, count) in dropped {
+            warn!(action="event_queue_overflow", dropped=count, "Event queue full, dropped {} events from the {} watcher, its bookmark won't move until the service restarts", count, watcher.name());
+            self.stats.events_dropped += count;
+            self.dropped_watchers.insert(watcher);
+        }
+    }
+
+    pub fn process(&mut self, queued: QueuedEvent) {
+        self.stats.events_processed += 1;
+        self.stats.last_event_time = queued.event.system.time_created().or(self.stats.last_event_time);
+        if !self.is_new(&queued) {
+            return;
+        }
+        match queued.watcher {
+            Watcher::LogonLogoff => {
+                let recorded = handle_logon_logoff_event(queued.event, &self.logon_logoff_config, &mut self.logons);
+                self.logons_changed |= self.logons.take_changed();
+                if recorded {
+                    self.collect_logs();
+                }
+            },
+            Watcher::Presence => {
+                handle_presence_event(queued.event);
+            },
+            Watcher::FailedLogon => {
+                handle_failed_logon_event(queued.event, &self.failed_logon_config);
+            },
+            Watcher::AuditIntegrity => {
+                handle_audit_integrity_event(queued.event);
+            }
+        }
+    }
+
+    /// Checks the event's record ID against the ones this watcher has already delivered.
+    fn is_new(&mut self, queued: &QueuedEvent) -> bool {
+        let system = &queued.event.system;
+        match self.record_ids.entry(queued.watcher).or_default().observe(&queued.event) {
+            Seen::Duplicate => {
+                debug!(action="record_id_duplicate", "Dropping {} already delivered to the {:?} watcher", system, queued.watcher);
+                return false;
+            },
+            Seen::LogCleared => warn!(action="record_id_reset", "Record IDs in {} restarted at {}, the log was cleared", system.channel.as_deref().unwrap_or_default(), system.event_record_id.unwrap_or_default()),
+            Seen::New => {}
+        }
+        if system.event_record_id.is_some() {
+            self.record_ids_changed = true;
+            self.bookmarks_changed.insert(queued.watcher);
+        }
+        true
+    }
+}
+
+/// The same test as the query built from `logon_types`, for events that did not come from a
+/// subscription. A custom `xpath` can't be evaluated here.
+pub fn matches_logon_logoff_query(event: &Event, config: &LogonLogoffConfig) -> bool {
+    match event.system.event_id {
+        4624 => event.event_data
+            .as_ref()
+            .and_then(|event_data| event_data.get_value("LogonType"))
+            .and_then(|logon_type| logon_type.parse::<u32>().ok())
+            .is_some_and(|logon_type| config.logon_types.contains(&logon_type)),
+        4647 => true,
+        _ => false
+    }
+}
+
+/// Filters and logs a logon/logoff event, matching logoffs to their logon in `logons`.
+/// Returns true if the event was recorded and the device state should be collected.
+pub fn handle_logon_logoff_event(event: Event, config: &LogonLogoffConfig, logons: &mut LogonTracker) -> bool {
+    // Recorded by their own watchers instead, in case a custom query matches them
+    let event_type = event.system.get_event_id_type();
+    if event_type.is_presence() || event_type == EventIdType::FailedLogon || IntegrityEventType::from_event_id(event.system.event_id).is_some() {
+        return false;
+    }
+    match &event.event_data {
+        Some(event_data) => {
+            if let Some(logon) = LogonEvent::from_event_data(event_data)
+                && !config.ignored_sid_prefixes.iter().any(|ignore_sid| {logon.target_user_sid.starts_with(ignore_sid)}) {
+                let time = event.time_created();
+                let correlation = logons.observe(event_type, time, &logon);
+                records::emit(&LogonLogoffRecord::new(event_type, logon)
+                    .with_system(&event.system)
+                    .with_correlation(time, correlation));
+                return true;
+            }
+            false
+        },
+        None => {
+            warn!(action="logon_logoff_event", "No Event Data for Event ID: {}", event.system.event_id);
+            false
+        }
+    }
+}
+
+/// Logs a lock, unlock or screensaver event, returning true if it was recorded.
+pub fn handle_presence_event(event: Event) -> bool {
+    let event_type = event.system.get_event_id_type();
+    if !event_type.is_presence() {
+        return false;
+    }
+    match event.event_data.as_ref().and_then(|event_data| event_data.get_value("TargetUserSid")) {
+        Some(sid) => {
+            let event_data = event.event_data.as_ref();
+            records::emit(&PresenceRecord {
+                event_type,
+                user_sid: sid,
+                username: event_data.and_then(|event_data| event_data.get_value("TargetUserName")),
+                session_id: event_data.and_then(|event_data| event_data.get_value("SessionId")).and_then(|session_id| session_id.parse().ok()),
+            });
+            true
+        },
+        None => {
+            warn!(action="presence_event", "No TargetUserSid for Event ID: {}", event.system.event_id);
+            false
+        }
+    }
+}
+
+/// Logs a failed logon with one of the configured logon types, returning true if it was recorded.
+pub fn handle_failed_logon_event(event: Event, config: &FailedLogonConfig) -> bool {
+    if event.system.get_event_id_type() != EventIdType::FailedLogon {
+        return false;
+    }
+    let Some(event_data) = event.event_data else {
+        warn!(action="failed_logon_event", "No Event Data for Event ID: {}", event.system.event_id);
+        return false;
+    };
+    let logon_type = event_data.get_value("LogonType");
+    if !logon_type.as_ref().and_then(|logon_type| logon_type.parse().ok()).is_some_and(|logon_type: u32| config.logon_types.contains(&logon_type)) {
+        return false;
+    }
+
+    let value = |name: &str| event_data.get_present_value(name);
+    let status = value("Status");
+    let sub_status = value("SubStatus");
+    records::emit(&FailedLogonRecord {
+        username: value("TargetUserName"),
+        domain: value("TargetDomainName"),
+        logon_type,
+        reason: ntstatus::failure_reason(status.as_deref(), sub_status.as_deref()).map(str::to_string),
+        status,
+        sub_status,
+        failure_reason: value("FailureReason").map(logon::decode_message),
+        ip_address: value("IpAddress"),
+        workstation_name: value("WorkstationName"),
+    });
+    true
+}
+
+/// Logs a log clear, event log shutdown or audit policy change, returning true if it was recorded.
+pub fn handle_audit_integrity_event(event: Event) -> bool {
+    match AuditIntegrityRecord::from_event(&event) {
+        Some(record) => {
+            records::emit(&record);
+            true
+        },
+        None => false
+    }
+}
+
+/// Records the console user and sessions, and the tenants when they have changed or are due
+/// a refresh.
+pub fn collect_logs(sessions: &dyn SessionProvider, join_info: &dyn JoinInfoProvider, tenants: &mut TenantCache) {
+    match join_info.get_entra_join_info() {
+        Err(error) => {
+            error!(action = "tenant_info", "Errror retrieving entra join info - {}", error);
+        },
+        Ok(results) => {
+            let update = tenants.update(&results, std::time::Instant::now());
+            if let Some(changed) = update.changed {
+                records::emit(&TenantJoinChangedRecord::from(changed));
+            }
+            if update.emit {
+                for result in results {
+                    records::emit(&TenantInfoRecord::from(result));
+                }
+            }
+        }
+    }
+
+    match sessions.get_user_info() {
+        Ok(current_user_info) => records::emit(&CurrentUserRecord::from(current_user_info)),
+        Err(err) => {
+            error!(action = "current_user_info", "Unable to retrieve user info: {}", err);
+        }
+    };
+
+    match sessions.list_sessions() {
+        Ok(current_sessions) => records::emit(&CurrentSessionsRecord { sessions: current_sessions.into_iter().map(Into::into).collect() }),
+        Err(err) => {
+            error!(action = "current_sessions_info", "Unable to list sessions: {}", err);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AuditIntegrityConfig, PresenceConfig, WatcherConfig};
+    use crate::events::debounce::DebounceStrategy;
+    use crate::events::query::QueryList;
+    use crate::platform::{AuditSetting, ConnectState, CurrentUserInfo, EntraJoinInfo, SessionChangeType, SessionInfo};
+    use crate::platform::fake::FakePlatform;
+    use crate::test_support::{capture_records, event, event_at, records_with_action};
+
+    const USER_SID: &str = "S-1-12-1-2991438786-1147252871-734652841-3570430303";
+
+    /// Registers the watchers, runs `emit` and processes whatever it queued the way
+    /// `service_loop` would.
+    fn watch<F: FnOnce()>(fake: &FakePlatform, config: &Config, emit: F) -> Vec<serde_json::Value> {
+        let (queue, mut events) = queue::event_queue(queue::DEFAULT_CAPACITY);
+        capture_records(|| {
+            let mut processor = EventProcessor::new(&fake.platform(), config);
+            let _watchers = register_watchers(&fake.platform(), config, &queue);
+            emit();
+            while let Ok(event) = events.try_recv() {
+                processor.process(event);
+            }
+            processor.save_state();
+        })
+    }
+
+    fn logon(sid: &str) -> Event {
+        event(4624, &[
+            ("TargetUserSid", sid),
+            ("TargetUserName", "JordanGomes"),
+            ("TargetLogonId", "0x4a0b2c"),
+            ("LogonType", "2"),
+            ("LogonProcessName", "User32 "),
+            ("AuthenticationPackageName", "Negotiate"),
+            ("IpAddress", "127.0.0.1"),
+            ("IpPort", "0"),
+            ("ElevatedToken", "%%1843"),
+        ])
+    }
+
+    fn logon_at(time: chrono::DateTime<chrono::Utc>) -> Event {
+        event_at(time, 4624, &[("TargetUserSid", USER_SID), ("TargetUserName", "JordanGomes"), ("LogonType", "2")])
+    }
+
+    #[test]
+    fn logon_is_logged_and_enriched() {
+        let fake = FakePlatform::default();
+        *fake.sessions.user.lock().unwrap() = Some(CurrentUserInfo {
+            sid: USER_SID.to_string(),
+            username: "test@jordangomes.com".to_string(),
+            user_type: "AzureAD".to_string(),
+            azure_ad_object_id: Some("b24dbbc2-b087-4461-a9e9-c92b5f71d0d4".to_string()),
+        });
+        fake.join_info.tenants.lock().unwrap().push(EntraJoinInfo {
+            tenant_id: "338f14b1-cb11-41e8-90ae-b06bc0fdd75a".to_string(),
+            device_id: "af4edcd4-4bb6-4679-8b0e-64c3262a1de4".to_string(),
+            registered_user: "test@jordangomes.com".to_string(),
+        });
+
+        let mut subscriptions = Vec::new();
+        let records = watch(&fake, &Config::default(), || {
+            subscriptions = fake.events.subscriptions();
+            fake.events.emit(logon(USER_SID));
+        });
+
+        assert_eq!(subscriptions.len(), 4);
+        assert_eq!(subscriptions[0].query, QueryList::channel("Security", &Config::default().logon_logoff.xpath()));
+        assert_eq!(subscriptions[1].name, "presence");
+        assert_eq!(subscriptions[2].query.selects[0].xpath, "Event[System[(EventID='4625')] and EventData[Data[@Name='LogonType']='2' or Data[@Name='LogonType']='7' or Data[@Name='LogonType']='10' or Data[@Name='LogonType']='11']]");
+        let logons = records_with_action(&records, "logon_logoff_event");
+        assert_eq!(logons.len(), 1);
+        assert_eq!(logons[0]["event_type"], "Logon");
+        assert_eq!(logons[0]["user_sid"], USER_SID);
+        assert_eq!(logons[0]["logon_type"], "2");
+        assert_eq!(logons[0]["logon_id"], "0x4a0b2c");
+        assert_eq!(logons[0]["logon_process"], "User32");
+        assert_eq!(logons[0]["authentication_package"], "Negotiate");
+        assert_eq!(logons[0]["elevated_token"], false);
+        assert_eq!(logons[0].get("ip_port"), None);
+        assert_eq!(records_with_action(&records, "tenant_info")[0]["device_id"], "af4edcd4-4bb6-4679-8b0e-64c3262a1de4");
+        assert_eq!(records_with_action(&records, "current_user_info")[0]["azure_ad_object_id"], "b24dbbc2-b087-4461-a9e9-c92b5f71d0d4");
+    }
+
+    #[test]
+    fn no_current_user_is_recorded_as_logged_out() {
+        let fake = FakePlatform::default();
+        let records = capture_records(|| collect_logs(fake.sessions.as_ref(), fake.join_info.as_ref(), &mut TenantCache::new(None)));
+
+        let current_user = records_with_action(&records, "current_user_info");
+        assert_eq!(current_user.len(), 1);
+        assert_eq!(current_user[0]["logged_in"], false);
+        assert_eq!(current_user[0].get("user_sid"), None);
+    }
+
+    #[test]
+    fn every_session_is_recorded() {
+        let fake = FakePlatform::default();
+        let user = CurrentUserInfo {
+            sid: USER_SID.to_string(),
+            username: "test@jordangomes.com".to_string(),
+            user_type: "AzureAD".to_string(),
+            azure_ad_object_id: Some("b24dbbc2-b087-4461-a9e9-c92b5f71d0d4".to_string()),
+        };
+        *fake.sessions.sessions.lock().unwrap() = vec![
+            SessionInfo { session_id: 0, station_name: "Services".to_string(), connect_state: ConnectState::Disconnected, client_name: None, client_address: None, user: None },
+            SessionInfo { session_id: 1, station_name: "Console".to_string(), connect_state: ConnectState::Active, client_name: None, client_address: None, user: None },
+            SessionInfo { session_id: 3, station_name: "RDP-Tcp#0".to_string(), connect_state: ConnectState::Active, client_name: Some("JORDANS-LAPTOP".to_string()), client_address: Some("10.0.0.12".to_string()), user: Some(user) },
+        ];
+        let records = capture_records(|| collect_logs(fake.sessions.as_ref(), fake.join_info.as_ref(), &mut TenantCache::new(None)));
+
+        let current_sessions = records_with_action(&records, "current_sessions_info");
+        assert_eq!(current_sessions.len(), 1);
+        let sessions = current_sessions[0]["sessions"].as_array().unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[0]["connect_state"], "Disconnected");
+        assert_eq!(sessions[1].get("user_sid"), None);
+        assert_eq!(sessions[2]["client_address"], "10.0.0.12");
+        assert_eq!(sessions[2]["user_sid"], USER_SID);
+        assert_eq!(sessions[2]["azure_ad_object_id"], "b24dbbc2-b087-4461-a9e9-c92b5f71d0d4");
+        assert_eq!(records_with_action(&records, "current_user_info")[0]["logged_in"], false);
+    }
+
+    #[test]
+    fn tenant_info_is_recorded_when_join_state_changes() {
+        let fake = FakePlatform::default();
+        let tenant = |device_id: &str| EntraJoinInfo {
+            tenant_id: "338f14b1-cb11-41e8-90ae-b06bc0fdd75a".to_string(),
+            device_id: device_id.to_string(),
+            registered_user: "test@jordangomes.com".to_string(),
+        };
+        *fake.join_info.tenants.lock().unwrap() = vec![tenant("af4edcd4-4bb6-4679-8b0e-64c3262a1de4")];
+        let mut tenants = TenantCache::new(None);
+        let mut collect = || capture_records(|| collect_logs(fake.sessions.as_ref(), fake.join_info.as_ref(), &mut tenants));
+
+        let first = collect();
+        assert_eq!(records_with_action(&first, "tenant_info").len(), 1);
+        assert!(records_with_action(&first, "tenant_join_changed").is_empty());
+        let unchanged = collect();
+        assert!(records_with_action(&unchanged, "tenant_info").is_empty());
+        assert_eq!(records_with_action(&unchanged, "current_user_info").len(), 1);
+
+        *fake.join_info.tenants.lock().unwrap() = vec![tenant("0b6f3c1e-5a8d-4f2b-8e4d-3c9a1b7e6f20")];
+        let rejoined = collect();
+        let changed = records_with_action(&rejoined, "tenant_join_changed");
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0]["rejoined"][0]["previous_device_id"], "af4edcd4-4bb6-4679-8b0e-64c3262a1de4");
+        assert_eq!(changed[0]["rejoined"][0]["device_id"], "0b6f3c1e-5a8d-4f2b-8e4d-3c9a1b7e6f20");
+        assert_eq!(records_with_action(&rejoined, "tenant_info")[0]["device_id"], "0b6f3c1e-5a8d-4f2b-8e4d-3c9a1b7e6f20");
+
+        *fake.join_info.tenants.lock().unwrap() = Vec::new();
+        let left = collect();
+        assert_eq!(records_with_action(&left, "tenant_join_changed")[0]["left"][0]["tenant_id"], "338f14b1-cb11-41e8-90ae-b06bc0fdd75a");
+        assert!(records_with_action(&left, "tenant_info").is_empty());
+    }
+
+    #[test]
+    fn tenant_changes_while_stopped_are_recorded() {
+        let fake = FakePlatform::default();
+        let tenant = |device_id: &str| EntraJoinInfo {
+            tenant_id: "338f14b1-cb11-41e8-90ae-b06bc0fdd75a".to_string(),
+            device_id: device_id.to_string(),
+            registered_user: "test@jordangomes.com".to_string(),
+        };
+        *fake.join_info.tenants.lock().unwrap() = vec![tenant("af4edcd4-4bb6-4679-8b0e-64c3262a1de4")];
+        let mut processor = EventProcessor::new(&fake.platform(), &Config::default());
+        let first = capture_records(|| processor.process(QueuedEvent { watcher: Watcher::LogonLogoff, event: logon(USER_SID) }));
+        assert!(records_with_action(&first, "tenant_join_changed").is_empty());
+        processor.save_state();
+        assert!(fake.state_dir.path().join("tenants.json").exists());
+
+        // Rejoined while the service was stopped
+        *fake.join_info.tenants.lock().unwrap() = vec![tenant("0b6f3c1e-5a8d-4f2b-8e4d-3c9a1b7e6f20")];
+        let mut processor = EventProcessor::new(&fake.platform(), &Config::default());
+        let restarted = capture_records(|| processor.process(QueuedEvent { watcher: Watcher::LogonLogoff, event: logon(USER_SID) }));
+        let changed = records_with_action(&restarted, "tenant_join_changed");
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0]["rejoined"][0]["previous_device_id"], "af4edcd4-4bb6-4679-8b0e-64c3262a1de4");
+        assert_eq!(records_with_action(&restarted, "tenant_info").len(), 1);
+    }
+
+    #[test]
+    fn queued_events_are_processed_by_service_loop() {
+        let fake = FakePlatform::default();
+        let (service_tx, service_rx) = std::sync::mpsc::channel();
+        let events = fake.events.clone();
+        let emitter = std::thread::spawn(move || {
+            while events.subscriptions().len() < 4 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            events.emit(logon(USER_SID));
+            events.emit(failed_logon("2"));
+            service_tx.send(ServiceMessage::Shutdown).unwrap();
+        });
+
+        let records = capture_records(|| service_loop(&fake.platform(), &Config::default(), &RotationErrors::default(), service_rx));
+        emitter.join().unwrap();
+
+        assert_eq!(records_with_action(&records, "logon_logoff_event").len(), 1);
+        assert_eq!(records_with_action(&records, "failed_logon_event").len(), 1);
+        assert_eq!(records_with_action(&records, "current_user_info").len(), 1);
+        assert_eq!(records_with_action(&records, "service_shutdown").len(), 1);
+        assert!(fake.events.subscriptions().is_empty());
+    }
+
+    #[test]
+    fn held_back_events_are_recorded_at_shutdown() {
+        let fake = FakePlatform::default();
+        let config = Config { logon_logoff: LogonLogoffConfig { watcher: WatcherConfig { debounce_ms: 60_000, debounce_strategy: DebounceStrategy::Trailing, ..LogonLogoffConfig::default().watcher }, ..LogonLogoffConfig::default() }, ..Config::default() };
+        let (service_tx, service_rx) = std::sync::mpsc::channel();
+        let events = fake.events.clone();
+        let emitter = std::thread::spawn(move || {
+            while events.subscriptions().len() < 4 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            events.emit(logon(USER_SID));
+            service_tx.send(ServiceMessage::Shutdown).unwrap();
+        });
+
+        let records = capture_records(|| service_loop(&fake.platform(), &config, &RotationErrors::default(), service_rx));
+        emitter.join().unwrap();
+
+        assert_eq!(records_with_action(&records, "logon_logoff_event").len(), 1);
+    }
+
+    #[test]
+    fn rotation_errors_are_logged_on_the_tick() {
+        let fake = FakePlatform::default();
+        let rotation_errors = RotationErrors::default();
+        rotation_errors.push("Unable to rotate cua.log - Access is denied".to_string());
+        let (service_tx, service_rx) = std::sync::mpsc::channel();
+        service_tx.send(ServiceMessage::Shutdown).unwrap();
+
+        let records = capture_records(|| service_loop(&fake.platform(), &Config::default(), &rotation_errors, service_rx));
+
+        let logged = records_with_action(&records, "log_rotation");
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0]["message"], "Unable to rotate cua.log - Access is denied");
+        assert!(rotation_errors.take().is_empty());
+    }
+
+    #[test]
+    fn session_changes_are_recorded_and_enriched() {
+        let fake = FakePlatform::default();
+        let (service_tx, service_rx) = std::sync::mpsc::channel();
+        service_tx.send(ServiceMessage::SessionChange(SessionChange { change_type: SessionChangeType::Unlock, session_id: 1 })).unwrap();
+        service_tx.send(ServiceMessage::Shutdown).unwrap();
+
+        let records = capture_records(|| service_loop(&fake.platform(), &Config::default(), &RotationErrors::default(), service_rx));
+
+        let changes = records_with_action(&records, "session_change_event");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0]["event_type"], "Unlock");
+        assert_eq!(changes[0]["session_id"], 1);
+        assert_eq!(records_with_action(&records, "current_user_info").len(), 1);
+        assert_eq!(records_with_action(&records, "service_shutdown").len(), 1);
+    }
+
+    #[test]
+    fn presence_events_are_recorded() {
+        let fake = FakePlatform::default();
+        let records = watch(&fake, &Config::default(), || {
+            fake.events.emit(event(4800, &[("TargetUserSid", USER_SID), ("TargetUserName", "JordanGomes"), ("TargetLogonId", "0x4a0b2c"), ("SessionId", "1")]));
+            fake.events.emit(event(4803, &[("TargetUserSid", USER_SID), ("TargetUserName", "JordanGomes"), ("SessionId", "1")]));
+        });
+
+        let presence = records_with_action(&records, "presence_event");
+        assert_eq!(presence.len(), 2);
+        assert_eq!(presence[0]["event_type"], "WorkstationLocked");
+        assert_eq!(presence[0]["user_sid"], USER_SID);
+        assert_eq!(presence[0]["session_id"], 1);
+        assert_eq!(presence[1]["event_type"], "ScreensaverDismissed");
+        // Presence doesn't go through the logon pipeline, and logons don't reach the presence handler
+        assert!(records_with_action(&records, "logon_logoff_event").is_empty());
+        assert!(!handle_presence_event(logon(USER_SID)));
+    }
+
+    #[test]
+    fn logoff_is_matched_to_logon_across_restarts() {
+        let logon_time = "2026-02-27T05:00:00Z".parse::<chrono::DateTime<chrono::Utc>>().unwrap();
+        let logoff = |minutes: i64, logon_id: &str| event_at(logon_time + chrono::TimeDelta::minutes(minutes), 4647, &[("TargetUserSid", USER_SID), ("TargetLogonId", logon_id)]);
+        let fake = FakePlatform::default();
+        watch(&fake, &Config::default(), || {
+            fake.events.emit(event_at(logon_time, 4624, &[("TargetUserSid", USER_SID), ("TargetLogonId", "0x4a0b2c"), ("LogonType", "10")]));
+        });
+
+        // A new event source, as after the service restarts
+        let restarted = FakePlatform { state_dir: fake.state_dir, ..FakePlatform::default() };
+        let records = watch(&restarted, &Config::default(), || {
+            restarted.events.emit(logoff(90, "0x4a0b2c"));
+            restarted.events.emit(logoff(95, "0x51f3a0"));
+        });
+
+        let logoffs = records_with_action(&records, "logon_logoff_event");
+        assert_eq!(logoffs.len(), 2);
+        assert_eq!(logoffs[0]["logon_matched"], true);
+        assert_eq!(logoffs[0]["logon_time"], "2026-02-27T05:00:00Z");
+        assert_eq!(logoffs[0]["logon_type"], "10");
+        assert_eq!(logoffs[0]["duration_secs"], 5400);
+        assert_eq!(logoffs[1]["logon_matched"], false);
+        assert_eq!(logoffs[1].get("duration_secs"), None);
+    }
+
+    #[test]
+    fn replayed_events_are_recorded_once() {
+        let start = "2026-02-27T05:00:00Z".parse::<chrono::DateTime<chrono::Utc>>().unwrap();
+        let logon = |record_id: u64, minutes: i64| {
+            let mut event = event_at(start + chrono::TimeDelta::minutes(minutes), 4624, &[("TargetUserSid", USER_SID), ("TargetLogonId", "0x4a0b2c"), ("LogonType", "2")]);
+            event.system.event_record_id = Some(record_id);
+            event.system.channel = Some("Security".to_string());
+            event
+        };
+        // The fake delivers every event to every watcher, leave only the one they're for
+        let config = Config {
+            presence: PresenceConfig { enabled: false, ..PresenceConfig::default() },
+            failed_logon: FailedLogonConfig { enabled: false, ..FailedLogonConfig::default() },
+            audit_integrity: AuditIntegrityConfig { enabled: false, ..AuditIntegrityConfig::default() },
+            ..Config::default()
+        };
+        let fake = FakePlatform::default();
+        let first = watch(&fake, &config, || {
+            fake.events.emit(logon(48211, 0));
+            fake.events.emit(logon(48212, 5));
+        });
+        assert_eq!(records_with_action(&first, "logon_logoff_event").len(), 2);
+
+        // The bookmark resumes a little early after the restart, then the log is cleared
+        let restarted = FakePlatform { state_dir: fake.state_dir, ..FakePlatform::default() };
+        let records = watch(&restarted, &config, || {
+            restarted.events.emit(logon(48212, 5));
+            restarted.events.emit(logon(48213, 10));
+            restarted.events.emit(logon(1, 20));
+        });
+
+        let logons = records_with_action(&records, "logon_logoff_event");
+        assert_eq!(logons.iter().map(|logon| logon["record_id"].as_u64().unwrap()).collect::<Vec<_>>(), vec![48213, 1]);
+        assert_eq!(records_with_action(&records, "record_id_reset").len(), 1);
+    }
+
+    #[test]
+    fn state_is_saved_once_per_batch() {
+        let fake = FakePlatform::default();
+        let mut processor = EventProcessor::new(&fake.platform(), &Config::default());
+        let record_ids = fake.state_dir.path().join("record_ids.json");
+        let open_logons = fake.state_dir.path().join("open_logons.json");
+        let mut logon = event_at("2026-02-27T05:00:00Z".parse().unwrap(), 4624, &[("TargetUserSid", USER_SID), ("TargetLogonId", "0x4a0b2c"), ("LogonType", "2")]);
+        logon.system.event_record_id = Some(48211);
+        logon.system.channel = Some("Security".to_string());
+
+        capture_records(|| processor.process(QueuedEvent { watcher: Watcher::LogonLogoff, event: logon.clone() }));
+        assert!(!record_ids.exists());
+        assert!(!open_logons.exists());
+        processor.save_state();
+        assert!(record_ids.exists());
+        assert!(open_logons.exists());
+
+        // A duplicate changes nothing, so nothing is written
+        std::fs::remove_file(&record_ids).unwrap();
+        std::fs::remove_file(&open_logons).unwrap();
+        capture_records(|| processor.process(QueuedEvent { watcher: Watcher::LogonLogoff, event: logon }));
+        processor.save_state();
+        assert!(!record_ids.exists());
+        assert!(!open_logons.exists());
+    }
+
+    #[test]
+    fn bookmarks_follow_processed_events() {
+        let fake = FakePlatform::default();
+        let mut processor = EventProcessor::new(&fake.platform(), &Config::default());
+        let bookmark = BookmarkStore::new(fake.state_dir.path(), "presence");
+        let mut lock = event_at("2026-02-27T05:00:00Z".parse().unwrap(), 4800, &[("TargetUserSid", USER_SID)]);
+        lock.system.event_record_id = Some(48230);
+        lock.system.channel = Some("Security".to_string());
+
+        processor.save_state();
+        assert_eq!(bookmark.load(), None);
+        capture_records(|| processor.process(QueuedEvent { watcher: Watcher::Presence, event: lock }));
+        assert_eq!(bookmark.load(), None);
+        processor.save_state();
+        assert_eq!(bookmark.load().as_deref(), Some("<BookmarkList>\r\n  <Bookmark Channel='Security' RecordId='48230' IsCurrent='true'/>\r\n</BookmarkList>"));
+        assert!(!fake.state_dir.path().join("logon_logoff.bookmark.xml").exists());
+    }
+
+    #[test]
+    fn dropped_events_stop_the_bookmark() {
+        let fake = FakePlatform::default();
+        let mut processor = EventProcessor::new(&fake.platform(), &Config::default());
+        let bookmark = BookmarkStore::new(fake.state_dir.path(), "presence");
+        let lock = |record_id: u64| {
+            let mut lock = event_at("2026-02-27T05:00:00Z".parse().unwrap(), 4800, &[("TargetUserSid", USER_SID)]);
+            lock.system.event_record_id = Some(record_id);
+            lock.system.channel = Some("Security".to_string());
+            QueuedEvent { watcher: Watcher::Presence, event: lock }
+        };
+        let saved = "<BookmarkList>\r\n  <Bookmark Channel='Security' RecordId='48230' IsCurrent='true'/>\r\n</BookmarkList>";
+
+        capture_records(|| processor.process(lock(48230)));
+        processor.save_state();
+        assert_eq!(bookmark.load().as_deref(), Some(saved));
+
+        // 48231 didn't fit in the queue
+        capture_records(|| processor.events_dropped(HashMap::from([(Watcher::Presence, 1)])));
+        capture_records(|| processor.process(lock(48232)));
+        processor.save_state();
+        assert_eq!(processor.stats.events_dropped, 1);
+        assert_eq!(bookmark.load().as_deref(), Some(saved));
+
+        // After a restart the dropped event is read again and recorded
+        let mut restarted = EventProcessor::new(&fake.platform(), &Config::default());
+        let records = capture_records(|| restarted.process(lock(48231)));
+        assert_eq!(records_with_action(&records, "presence_event").len(), 1);
+    }
+
+    #[test]
+    fn log_clears_and_audit_policy_changes_are_recorded() {
+        let fake = FakePlatform::default();
+        let mut log_cleared: Event = quick_xml::de::from_str("<Event><System><EventID>1102</EventID><EventRecordID>9002</EventRecordID><Channel>Security</Channel></System><UserData><LogFileCleared xmlns='http://manifests.microsoft.com/win/2004/08/windows/eventlog'><SubjectUserSid>S-1-5-21-406160441-2633804267-1261186540-1001</SubjectUserSid><SubjectUserName>jordan</SubjectUserName><SubjectDomainName>JORDANS-DESKTOP</SubjectDomainName><SubjectLogonId>0x4a0b2c</SubjectLogonId></LogFileCleared></UserData></Event>").unwrap();
+        log_cleared.system.time_created = event_at("2026-02-27T05:00:00Z".parse().unwrap(), 1102, &[]).system.time_created;
+        let records = watch(&fake, &Config::default(), || {
+            fake.events.emit(log_cleared);
+            fake.events.emit(event(4719, &[
+                ("SubjectUserSid", "S-1-5-18"),
+                ("SubjectUserName", "JORDANS-DESKTOP$"),
+                ("SubjectLogonId", "0x3e7"),
+                ("CategoryId", "%%8273"),
+                ("SubcategoryId", "%%12544"),
+                ("SubcategoryGuid", "{0cce9215-69ae-11d9-bed3-505054503030}"),
+                ("AuditPolicyChanges", "%%8448, %%8450"),
+            ]));
+        });
+
+        let integrity = records_with_action(&records, "audit_integrity_event");
+        assert_eq!(integrity.len(), 2);
+        assert_eq!(integrity[0]["event_type"], "LogCleared");
+        assert_eq!(integrity[0]["user_sid"], "S-1-5-21-406160441-2633804267-1261186540-1001");
+        assert_eq!(integrity[0]["username"], "jordan");
+        assert_eq!(integrity[0]["record_id"], 9002);
+        assert_eq!(integrity[0]["event_time"], "2026-02-27T05:00:00Z");
+        assert_eq!(integrity[1]["event_type"], "AuditPolicyChanged");
+        assert_eq!(integrity[1]["category"], "Logon/Logoff");
+        assert_eq!(integrity[1]["subcategory"], "Logon");
+        assert_eq!(integrity[1]["changes"], serde_json::json!(["Success removed", "Failure removed"]));
+        assert!(records_with_action(&records, "logon_logoff_event").is_empty());
+    }
+
+    #[test]
+    fn audit_policy_reports_settings_watchers_need() {
+        let fake = FakePlatform::default();
+        let records = capture_records(|| check_audit_policy(fake.audit_policy.as_ref(), &Config::default()));
+        let status = records_with_action(&records, "audit_policy_status");
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0]["subcategories"].as_array().unwrap().len(), audit_policy::SUBCATEGORIES.len());
+        assert_eq!(status[0]["subcategories"][0], serde_json::json!({"subcategory": "Logon", "guid": "0cce9215-69ae-11d9-bed3-505054503030", "success": true, "failure": true}));
+        assert!(status[0]["missing"].as_array().unwrap().is_empty());
+        assert!(records_with_action(&records, "audit_policy_missing").is_empty());
+
+        // Logon failures and Other Logon/Logoff Events not audited, with presence turned off
+        fake.audit_policy.settings.lock().unwrap().extend([
+            ("0cce9215-69ae-11d9-bed3-505054503030".to_string(), AuditSetting { success: true, failure: false }),
+            ("0cce921c-69ae-11d9-bed3-505054503030".to_string(), AuditSetting { success: false, failure: false }),
+        ]);
+        let config = Config { presence: PresenceConfig { enabled: false, ..PresenceConfig::default() }, ..Config::default() };
+        let records = capture_records(|| check_audit_policy(fake.audit_policy.as_ref(), &config));
+
+        let missing = records_with_action(&records, "audit_policy_status")[0]["missing"].as_array().unwrap().clone();
+        assert_eq!(missing, vec![serde_json::json!({"subcategory": "Logon", "outcome": "Failure", "event_ids": [4625], "watcher": "failed_logon"})]);
+        assert_eq!(records_with_action(&records, "audit_policy_missing").len(), 1);
+    }
+
+    #[test]
+    fn presence_watcher_can_be_turned_off() {
+        let fake = FakePlatform::default();
+        let config = Config { presence: PresenceConfig { enabled: false, ..PresenceConfig::default() }, ..Config::default() };
+        let (queue, _events) = queue::event_queue(queue::DEFAULT_CAPACITY);
+        let watchers = register_watchers(&fake.platform(), &config, &queue);
+
+        assert_eq!(watchers.len(), 3);
+        assert_eq!(fake.events.subscriptions().len(), 3);
+        drop(watchers);
+        assert!(fake.events.subscriptions().is_empty());
+    }
+
+    fn failed_logon(logon_type: &str) -> Event {
+        event(4625, &[
+            ("SubjectUserSid", "S-1-5-18"),
+            ("TargetUserSid", "S-1-0-0"),
+            ("TargetUserName", "jordan"),
+            ("TargetDomainName", "JORDANS-DESKTOP"),
+            ("Status", "0xc000006d"),
+            ("FailureReason", "%%2313"),
+            ("SubStatus", "0xc000006a"),
+            ("LogonType", logon_type),
+            ("WorkstationName", "JORDANS-DESKTOP"),
+            ("IpAddress", "-"),
+            ("IpPort", "-"),
+        ])
+    }
+
+    #[test]
+    fn failed_logons_are_recorded_with_reason() {
+        let fake = FakePlatform::default();
+        let records = watch(&fake, &Config::default(), || {
+            fake.events.emit(failed_logon("2"));
+            fake.events.emit(failed_logon("3"));
+        });
+
+        let failed = records_with_action(&records, "failed_logon_event");
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0]["username"], "jordan");
+        assert_eq!(failed[0]["domain"], "JORDANS-DESKTOP");
+        assert_eq!(failed[0]["status"], "0xc000006d");
+        assert_eq!(failed[0]["sub_status"], "0xc000006a");
+        assert_eq!(failed[0]["reason"], "Wrong password");
+        assert_eq!(failed[0]["failure_reason"], "Unknown user name or bad password");
+        assert_eq!(failed[0]["workstation_name"], "JORDANS-DESKTOP");
+        assert_eq!(failed[0].get("ip_address"), None);
+        // Failed logons never reach the logon pipeline
+        assert!(records_with_action(&records, "logon_logoff_event").is_empty());
+        assert!(records_with_action(&records, "current_user_info").is_empty());
+    }
+
+    #[test]
+    fn whitelisted_sids_are_ignored() {
+        let config = LogonLogoffConfig::default();
+        let records = capture_records(|| {
+            assert!(!handle_logon_logoff_event(logon("S-1-5-96-0-1"), &config, &mut LogonTracker::default()));
+            assert!(!handle_logon_logoff_event(logon("S-1-5-90-0-1"), &config, &mut LogonTracker::default()));
+        });
+
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn events_inside_debounce_window_are_dropped() {
+        let fake = FakePlatform::default();
+        let records = watch(&fake, &Config::default(), || {
+            let start = chrono::Utc::now();
+            fake.events.emit(logon_at(start));
+            fake.events.emit(logon_at(start + Duration::from_millis(10)));
+            fake.events.emit(logon_at(start + Duration::from_millis(100)));
+        });
+
+        assert_eq!(records_with_action(&records, "logon_logoff_event").len(), 2);
+    }
+
+    #[test]
+    fn sid_debounce_keeps_other_users_events() {
+        let fake = FakePlatform::default();
+        let config = Config { logon_logoff: LogonLogoffConfig { watcher: WatcherConfig { debounce_strategy: DebounceStrategy::Sid, ..LogonLogoffConfig::default().watcher }, ..LogonLogoffConfig::default() }, ..Config::default() };
+        let other_sid = "S-1-5-21-406160441-2633804267-1261186540-1001";
+        let records = watch(&fake, &config, || {
+            let start = chrono::Utc::now();
+            fake.events.emit(logon_at(start));
+            fake.events.emit(event_at(start + Duration::from_millis(10), 4647, &[("TargetUserSid", USER_SID)]));
+            fake.events.emit(event_at(start + Duration::from_millis(20), 4624, &[("TargetUserSid", other_sid), ("LogonType", "2")]));
+            fake.events.flush();
+        });
+
+        let events = records_with_action(&records, "logon_logoff_event");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event_type"], "LogoffInteractive");
+        assert_eq!(events[1]["user_sid"], other_sid);
+    }
+
+    #[test]
+    fn query_matches_interactive_logons_and_logoffs() {
+        let config = LogonLogoffConfig::default();
+        assert!(matches_logon_logoff_query(&logon(USER_SID), &config));
+        assert!(matches_logon_logoff_query(&event(4647, &[("TargetUserSid", USER_SID)]), &config));
+        assert!(!matches_logon_logoff_query(&event(4624, &[("TargetUserSid", USER_SID), ("LogonType", "3")]), &config));
+        assert!(!matches_logon_logoff_query(&event(4634, &[("TargetUserSid", USER_SID)]), &config));
+
+        let network_logons = LogonLogoffConfig { logon_types: vec![3], ..LogonLogoffConfig::default() };
+        assert!(matches_logon_logoff_query(&event(4624, &[("TargetUserSid", USER_SID), ("LogonType", "3")]), &network_logons));
+    }
+
+    #[test]
+    fn ignored_sid_prefixes_come_from_config() {
+        let config = LogonLogoffConfig { ignored_sid_prefixes: vec!["S-1-12-1-".to_string()], ..LogonLogoffConfig::default() };
+        capture_records(|| {
+            assert!(!handle_logon_logoff_event(logon(USER_SID), &config, &mut LogonTracker::default()));
+            assert!(handle_logon_logoff_event(logon("S-1-5-90-0-1"), &config, &mut LogonTracker::default()));
+        });
+    }
+
+    #[test]
+    fn missing_event_data_is_not_recorded() {
+        let mut no_data = logon(USER_SID);
+        no_data.event_data = None;
+        let records = capture_records(|| {
+            assert!(!handle_logon_logoff_event(no_data, &LogonLogoffConfig::default(), &mut LogonTracker::default()));
+        });
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["level"], "WARN");
+    }
+}
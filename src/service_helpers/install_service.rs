@@ -1,3 +1,4 @@
+#[cfg(windows)]
 mod config;
 
 #[cfg(windows)]
@@ -30,4 +31,9 @@ fn main() -> windows_service::Result<()> {
     let service = service_manager.create_service(&service_info, ServiceAccess::CHANGE_CONFIG)?;
     service.set_description(config::SERVICE_DESCRIPTION)?;
     Ok(())
+}
+
+#[cfg(not(windows))]
+fn main() {
+    panic!("This program is only intended to run on Windows.");
 }
\ No newline at end of file
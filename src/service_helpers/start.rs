@@ -1,11 +1,19 @@
+#[cfg(windows)]
 mod config;
-use std::ffi::OsStr;
-use windows_service::service::ServiceAccess;
-use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
 
+#[cfg(windows)]
 fn main() -> windows_service::Result<()>  {
+    use std::ffi::OsStr;
+    use windows_service::service::ServiceAccess;
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
     let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
     let my_service = manager.open_service(config::SERVICE_NAME, ServiceAccess::START)?;
     my_service.start(&[OsStr::new("")])?;
     Ok(())
 }
+
+#[cfg(not(windows))]
+fn main() {
+    panic!("This program is only intended to run on Windows.");
+}
@@ -0,0 +1,526 @@
+//! Pairs `logon_logoff_event` records into sessions, either as they are emitted or from an
+//! existing `cua.log` and its rotated files. `cua sessions [--from <time>] [--to <time>]`
+//! prints the sessions overlapping a time range as `session` records.
+//!
+//! A logoff closes the session with its `logon_id`, along with the other half of a split admin
+//! token. Records written before logon IDs were recorded only carry the user's SID, those
+//! logoffs close that user's most recent open session.
+//! Sessions still open when the service starts again are closed at the last time the service
+//! was seen running, as the device may have lost power. If the user's next record turns out to
+//! be a logoff or an unlock the session is picked up again, it was only the service that restarted.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use flate2::read::GzDecoder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::events::EventIdType;
+use crate::logging::json_subscriber;
+use crate::logging::rotation::rotated_files;
+use crate::platform;
+use crate::records::{self, LogonLogoffRecord, Record};
+
+/// Logon type Windows uses when a locked session is unlocked.
+const UNLOCK_LOGON_TYPE: &str = "7";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum SessionEnd {
+    Logoff,
+    /// Still open when the service restarted and never seen again.
+    MissingLogoff,
+    /// Still open at the end of the log.
+    Open,
+}
+
+/// A logon paired with its logoff.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SessionRecord {
+    pub user_sid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logon_id: Option<String>,
+    /// The other half of a split admin token, closed by the same logoff.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linked_logon_id: Option<String>,
+    /// Missing when the logon happened before the log starts.
+    pub start: Option<DateTime<Utc>>,
+    /// The logoff, or the last time the service was running for `MissingLogoff`.
+    pub end: Option<DateTime<Utc>>,
+    pub duration_secs: Option<i64>,
+    pub end_reason: SessionEnd,
+}
+
+impl Record for SessionRecord {
+    const ACTION: &'static str = "session";
+}
+
+impl SessionRecord {
+    fn close(&mut self, end: Option<DateTime<Utc>>, end_reason: SessionEnd) {
+        self.end = end;
+        self.end_reason = end_reason;
+        self.duration_secs = self.start.zip(end).map(|(start, end)| (end - start).num_seconds());
+    }
+
+    /// Whether any part of the session falls inside `from..to`.
+    pub fn overlaps(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> bool {
+        let starts_before_to = match (self.start, to) {
+            (Some(start), Some(to)) => start < to,
+            _ => true
+        };
+        let ends_after_from = match (self.end, from) {
+            (Some(end), Some(from)) => end > from,
+            _ => true
+        };
+        starts_before_to && ends_after_from
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SessionBuilder {
+    sessions: Vec<SessionRecord>,
+    /// Indexes of the sessions still open.
+    open: Vec<usize>,
+    /// Indexes of sessions closed by a service restart that can still be picked up again.
+    interrupted: Vec<usize>,
+    last_seen: Option<DateTime<Utc>>,
+}
+
+impl SessionBuilder {
+    /// Adds a line from `cua.log`, anything other than logon events and service starts is
+    /// only used to track when the service was last running.
+    pub fn push_line(&mut self, line: &Value) {
+        let Some(at) = line.get("timestamp").and_then(|timestamp| serde_json::from_value::<DateTime<Utc>>(timestamp.clone()).ok()) else {
+            return;
+        };
+        match line.get("action").and_then(Value::as_str) {
+            Some(LogonLogoffRecord::ACTION) => {
+                if let Ok(record) = serde_json::from_value::<LogonLogoffRecord>(line.clone()) {
+                    // Events read back after a restart are placed at the time Windows raised them
+                    self.push_event(record.event_time.unwrap_or(at), &record);
+                    self.seen(at);
+                }
+            },
+            Some("service_startup") => self.service_started(at),
+            _ => self.seen(at)
+        }
+    }
+
+    pub fn push_event(&mut self, at: DateTime<Utc>, record: &LogonLogoffRecord) {
+        match record.event_type {
+            EventIdType::Logon => self.logon(at, record),
+            EventIdType::Logoff | EventIdType::LogoffInteractive => self.logoff(at, record),
+            _ => {}
+        }
+        self.seen(at);
+    }
+
+    /// Closes every open session at the last time the service was seen running.
+    pub fn service_started(&mut self, at: DateTime<Utc>) {
+        for index in std::mem::take(&mut self.open) {
+            self.sessions[index].close(self.last_seen, SessionEnd::MissingLogoff);
+            self.interrupted.push(index);
+        }
+        self.seen(at);
+    }
+
+    /// Every session seen so far by start time, sessions still open are closed as `Open`.
+    pub fn finish(mut self) -> Vec<SessionRecord> {
+        for index in self.open {
+            self.sessions[index].close(None, SessionEnd::Open);
+        }
+        self.sessions.sort_by_key(|session| session.start);
+        self.sessions
+    }
+
+    fn logon(&mut self, at: DateTime<Utc>, record: &LogonLogoffRecord) {
+        if record.logon_type.as_deref() == Some(UNLOCK_LOGON_TYPE) {
+            if self.latest(&self.open, &record.user_sid).is_some() {
+                return;
+            }
+            if let Some(position) = self.latest(&self.interrupted, &record.user_sid) {
+                let index = self.interrupted.remove(position);
+                self.sessions[index].close(None, SessionEnd::Open);
+                self.open.push(index);
+                return;
+            }
+        }
+
+        // A new logon means the user's sessions from before a restart really did end
+        self.interrupted.retain(|&index| self.sessions[index].user_sid != record.user_sid);
+        self.open.push(self.sessions.len());
+        self.sessions.push(SessionRecord {
+            user_sid: record.user_sid.clone(),
+            username: record.username.clone(),
+            logon_type: record.logon_type.clone(),
+            logon_id: record.logon_id.clone(),
+            linked_logon_id: record.linked_logon_id.clone(),
+            start: Some(at),
+            end: None,
+            duration_secs: None,
+            end_reason: SessionEnd::Open,
+        });
+    }
+
+    fn logoff(&mut self, at: DateTime<Utc>, record: &LogonLogoffRecord) {
+        if let Some(position) = self.ended_by(&self.open, record) {
+            let index = self.open.remove(position);
+            self.sessions[index].close(Some(at), SessionEnd::Logoff);
+            self.close_linked(index, at);
+        } else if let Some(position) = self.ended_by(&self.interrupted, record) {
+            let index = self.interrupted.remove(position);
+            self.sessions[index].close(Some(at), SessionEnd::Logoff);
+        } else {
+            let mut session = SessionRecord {
+                user_sid: record.user_sid.clone(),
+                username: record.username.clone(),
+                logon_type: None,
+                logon_id: record.logon_id.clone(),
+                linked_logon_id: None,
+                start: None,
+                end: None,
+                duration_secs: None,
+                end_reason: SessionEnd::Open,
+            };
+            session.close(Some(at), SessionEnd::Logoff);
+            self.sessions.push(session);
+        }
+    }
+
+    /// Closes the open half of a split admin token whose other half the logoff ended.
+    fn close_linked(&mut self, index: usize, at: DateTime<Utc>) {
+        let closed = &self.sessions[index];
+        let is_linked = |session: &SessionRecord| session.user_sid == closed.user_sid
+            && (closed.linked_logon_id.is_some() && session.logon_id == closed.linked_logon_id
+                || closed.logon_id.is_some() && session.linked_logon_id == closed.logon_id);
+        if let Some(position) = self.open.iter().position(|&open| is_linked(&self.sessions[open])) {
+            let linked = self.open.remove(position);
+            self.sessions[linked].close(Some(at), SessionEnd::Logoff);
+        }
+    }
+
+    fn seen(&mut self, at: DateTime<Utc>) {
+        self.last_seen = self.last_seen.max(Some(at));
+    }
+
+    /// Position in `indexes` of the session a logoff ends, the one with its logon ID. Logoffs
+    /// without one, and sessions logged without one, fall back to the user's latest session.
+    fn ended_by(&self, indexes: &[usize], record: &LogonLogoffRecord) -> Option<usize> {
+        let Some(logon_id) = &record.logon_id else {
+            return self.latest(indexes, &record.user_sid);
+        };
+        let find = |matches: &dyn Fn(&SessionRecord) -> bool| indexes.iter().rposition(|&index| {
+            let session = &self.sessions[index];
+            session.user_sid == record.user_sid && matches(session)
+        });
+        find(&|session| session.logon_id.as_ref() == Some(logon_id) || session.linked_logon_id.as_ref() == Some(logon_id))
+            .or_else(|| find(&|session| session.logon_id.is_none()))
+    }
+
+    /// Position in `indexes` of the most recently started session for `user_sid`.
+    fn latest(&self, indexes: &[usize], user_sid: &str) -> Option<usize> {
+        indexes.iter().rposition(|&index| self.sessions[index].user_sid == user_sid)
+    }
+}
+
+/// Builds sessions from log lines, skipping any that aren't JSON.
+pub fn sessions_from_lines(builder: &mut SessionBuilder, reader: impl BufRead) -> std::io::Result<()> {
+    for line in reader.lines() {
+        if let Ok(line) = serde_json::from_str::<Value>(&line?) {
+            builder.push_line(&line);
+        }
+    }
+    Ok(())
+}
+
+/// Builds sessions from `name` in `log_dir` and its rotated files, oldest first.
+pub fn sessions_from_log(log_dir: &Path, name: &str) -> std::io::Result<Vec<SessionRecord>> {
+    let mut builder = SessionBuilder::default();
+    let mut files = rotated_files(log_dir, name)?;
+    files.push(log_dir.join(name));
+    for path in files {
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err)
+        };
+        let reader: Box<dyn Read> = match path.extension() {
+            Some(extension) if extension == "gz" => Box::new(GzDecoder::new(file)),
+            _ => Box::new(file)
+        };
+        sessions_from_lines(&mut builder, BufReader::new(reader))?;
+    }
+    Ok(builder.finish())
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SessionsArgs {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Parses `[--from <time>] [--to <time>]`.
+pub fn parse_args(args: &[String]) -> Result<SessionsArgs, String> {
+    let mut parsed = SessionsArgs::default();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        match flag.as_str() {
+            "--from" => parsed.from = Some(parse_time(value)?),
+            "--to" => parsed.to = Some(parse_time(value)?),
+            _ => return Err(format!("Unknown option {}, usage: cua sessions [--from <time>] [--to <time>]", flag))
+        }
+    }
+    Ok(parsed)
+}
+
+/// RFC 3339, or `YYYY-MM-DD[ HH:MM[:SS]]` in local time.
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.to_utc());
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)));
+    naive
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|time| time.to_utc())
+        .ok_or_else(|| format!("Unable to parse time '{}', use 2026-02-27 09:00 or RFC 3339", value))
+}
+
+pub fn run(args: &[String], log_dir: &Path) -> platform::Result<()> {
+    let args = parse_args(args)?;
+    let sessions = sessions_from_log(log_dir, "cua.log")?;
+    tracing::subscriber::with_default(json_subscriber(std::io::stdout), || {
+        for session in sessions.iter().filter(|session| session.overlaps(args.from, args.to)) {
+            records::emit(session);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use serde_json::json;
+    use std::io::Write;
+
+    const ALICE: &str = "S-1-12-1-2991438786-1147252871-734652841-3570430303";
+    const BOB: &str = "S-1-5-21-406160441-2633804267-1261186540-1001";
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 2, 24, hour, minute, 0).unwrap()
+    }
+
+    fn line(time: DateTime<Utc>, event_type: &str, sid: &str, logon_type: Option<&str>) -> Value {
+        let mut line = json!({
+            "timestamp": time,
+            "level": "INFO",
+            "action": "logon_logoff_event",
+            "schema_version": 1,
+            "event_type": event_type,
+            "user_sid": sid,
+            "username": "JordanGomes",
+            "target": "cua::records",
+        });
+        if let Some(logon_type) = logon_type {
+            line["logon_type"] = logon_type.into();
+        }
+        line
+    }
+
+    fn logon(time: DateTime<Utc>, sid: &str, logon_type: &str) -> Value {
+        line(time, "Logon", sid, Some(logon_type))
+    }
+
+    fn with_logon_id(mut line: Value, logon_id: &str, linked_logon_id: Option<&str>) -> Value {
+        line["logon_id"] = logon_id.into();
+        if let Some(linked_logon_id) = linked_logon_id {
+            line["linked_logon_id"] = linked_logon_id.into();
+        }
+        line
+    }
+
+    fn logoff(time: DateTime<Utc>, sid: &str) -> Value {
+        line(time, "LogoffInteractive", sid, None)
+    }
+
+    fn action(time: DateTime<Utc>, action: &str) -> Value {
+        json!({ "timestamp": time, "level": "INFO", "action": action, "message": "", "target": "cua" })
+    }
+
+    fn build(lines: &[Value]) -> Vec<SessionRecord> {
+        let mut builder = SessionBuilder::default();
+        for line in lines {
+            builder.push_line(line);
+        }
+        builder.finish()
+    }
+
+    #[test]
+    fn logons_are_paired_with_logoffs() {
+        let sessions = build(&[
+            logon(at(9, 0), ALICE, "2"),
+            logon(at(11, 0), ALICE, "7"),
+            logoff(at(17, 0), ALICE),
+        ]);
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].start, Some(at(9, 0)));
+        assert_eq!(sessions[0].end, Some(at(17, 0)));
+        assert_eq!(sessions[0].duration_secs, Some(8 * 3600));
+        assert_eq!(sessions[0].logon_type.as_deref(), Some("2"));
+        assert_eq!(sessions[0].end_reason, SessionEnd::Logoff);
+    }
+
+    #[test]
+    fn delayed_events_are_placed_at_event_time() {
+        let mut late_logoff = logoff(at(12, 5), ALICE);
+        late_logoff["event_time"] = json!(at(11, 40));
+        let sessions = build(&[
+            logon(at(9, 0), ALICE, "2"),
+            action(at(11, 30), "service_stopped"),
+            action(at(12, 0), "service_startup"),
+            late_logoff,
+        ]);
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].end, Some(at(11, 40)));
+        assert_eq!(sessions[0].end_reason, SessionEnd::Logoff);
+    }
+
+    #[test]
+    fn overlapping_sessions_are_kept_apart() {
+        let sessions = build(&[
+            logon(at(9, 0), ALICE, "2"),
+            logon(at(9, 30), BOB, "10"),
+            logon(at(10, 0), ALICE, "10"),
+            logoff(at(10, 30), ALICE),
+            logoff(at(11, 0), BOB),
+        ]);
+
+        let ends: Vec<_> = sessions.iter().map(|session| (session.user_sid.as_str(), session.logon_type.as_deref(), session.end)).collect();
+        assert_eq!(ends, vec![
+            (ALICE, Some("2"), None),
+            (BOB, Some("10"), Some(at(11, 0))),
+            (ALICE, Some("10"), Some(at(10, 30))),
+        ]);
+        assert_eq!(sessions[0].end_reason, SessionEnd::Open);
+    }
+
+    #[test]
+    fn logoffs_are_matched_by_logon_id() {
+        let sessions = build(&[
+            with_logon_id(logon(at(9, 0), ALICE, "2"), "0x1a", None),
+            with_logon_id(logon(at(10, 0), ALICE, "10"), "0x2b", None),
+            with_logon_id(logoff(at(11, 0), ALICE), "0x1a", None),
+            // Split admin token, the logoff carries the ID of one half
+            with_logon_id(logon(at(12, 0), ALICE, "2"), "0x3c", Some("0x3d")),
+            with_logon_id(logon(at(12, 0), ALICE, "2"), "0x3d", Some("0x3c")),
+            with_logon_id(logoff(at(13, 0), ALICE), "0x3d", None),
+        ]);
+
+        let ends: Vec<_> = sessions.iter().map(|session| (session.logon_id.as_deref(), session.end)).collect();
+        assert_eq!(ends, vec![
+            (Some("0x1a"), Some(at(11, 0))),
+            (Some("0x2b"), None),
+            (Some("0x3c"), Some(at(13, 0))),
+            (Some("0x3d"), Some(at(13, 0))),
+        ]);
+        assert_eq!(sessions[1].end_reason, SessionEnd::Open);
+
+        // Logons recorded without a logon ID are still closed by the user's logoff
+        let sessions = build(&[logon(at(9, 0), ALICE, "2"), with_logon_id(logoff(at(10, 0), ALICE), "0x1a", None)]);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].end_reason, SessionEnd::Logoff);
+    }
+
+    #[test]
+    fn power_loss_closes_sessions_at_last_seen() {
+        let sessions = build(&[
+            logon(at(9, 0), ALICE, "2"),
+            action(at(12, 0), "current_user_info"),
+            action(at(14, 0), "service_startup"),
+            logon(at(14, 5), ALICE, "2"),
+            logoff(at(17, 0), ALICE),
+        ]);
+
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].end, Some(at(12, 0)));
+        assert_eq!(sessions[0].end_reason, SessionEnd::MissingLogoff);
+        assert_eq!(sessions[0].duration_secs, Some(3 * 3600));
+        assert_eq!(sessions[1].start, Some(at(14, 5)));
+        assert_eq!(sessions[1].end_reason, SessionEnd::Logoff);
+    }
+
+    #[test]
+    fn sessions_survive_a_service_restart() {
+        let sessions = build(&[
+            logon(at(9, 0), ALICE, "2"),
+            logon(at(9, 10), BOB, "2"),
+            action(at(12, 0), "service_stopped"),
+            action(at(12, 1), "service_startup"),
+            logon(at(13, 0), BOB, "7"),
+            logoff(at(17, 0), ALICE),
+        ]);
+
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].end, Some(at(17, 0)));
+        assert_eq!(sessions[0].end_reason, SessionEnd::Logoff);
+        assert_eq!(sessions[1].end, None);
+        assert_eq!(sessions[1].end_reason, SessionEnd::Open);
+    }
+
+    #[test]
+    fn logoff_without_logon_has_no_start() {
+        let sessions = build(&[logoff(at(9, 0), ALICE)]);
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].start, None);
+        assert_eq!(sessions[0].end, Some(at(9, 0)));
+        assert_eq!(sessions[0].duration_secs, None);
+    }
+
+    #[test]
+    fn reads_rotated_logs_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rotated = GzEncoder::new(File::create(dir.path().join("cua.log.20260224-120000-000000.gz")).unwrap(), Compression::default());
+        writeln!(rotated, "{}", logon(at(9, 0), ALICE, "2")).unwrap();
+        rotated.finish().unwrap();
+        std::fs::write(dir.path().join("cua.log"), format!("not json\n{}\n", logoff(at(17, 0), ALICE))).unwrap();
+
+        let sessions = sessions_from_log(dir.path(), "cua.log").unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].duration_secs, Some(8 * 3600));
+    }
+
+    #[test]
+    fn range_selects_overlapping_sessions() {
+        let sessions = build(&[
+            logon(at(7, 0), ALICE, "2"),
+            logoff(at(8, 0), ALICE),
+            logon(at(8, 30), BOB, "2"),
+            logoff(at(9, 30), BOB),
+            logon(at(16, 0), ALICE, "2"),
+        ]);
+        let args = parse_args(&["--from".to_string(), "2026-02-24T09:00:00Z".to_string(), "--to".to_string(), "2026-02-24T17:00:00Z".to_string()]).unwrap();
+
+        let selected: Vec<_> = sessions.iter().filter(|session| session.overlaps(args.from, args.to)).map(|session| session.start).collect();
+        assert_eq!(selected, vec![Some(at(8, 30)), Some(at(16, 0))]);
+        assert!(parse_args(&["--from".to_string()]).is_err());
+        assert!(parse_args(&["--since".to_string(), "2026-02-24".to_string()]).is_err());
+        assert_eq!(parse_time("2026-02-24 09:00").unwrap(), parse_time("2026-02-24T09:00").unwrap());
+        assert_eq!(parse_time("2026-02-24 09:00").unwrap() - parse_time("2026-02-24").unwrap(), TimeDelta::hours(9));
+    }
+}
@@ -0,0 +1,363 @@
+//! Outputs records are sent to on top of `cua.log`. `SinkLayer` picks the records emitted by
+//! `records::emit` out of the tracing stream and queues them for a worker thread per sink,
+//! which sends them in batches. Diagnostic logging only goes to `cua.log`. When a sink fails
+//! the batch is spooled to disk and retried with backoff, spooled records are always sent
+//! before newer ones.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError, sync_channel};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tracing::{Level, Subscriber, info, warn};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+
+use crate::config::Config;
+use crate::logging::event_json;
+use crate::platform;
+
+pub mod spool;
+pub mod webhook;
+
+use spool::Spool;
+
+const QUEUE_CAPACITY: usize = 10_000;
+
+pub trait Sink: Send {
+    fn name(&self) -> &str;
+    fn send(&mut self, records: &[Value]) -> platform::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Backoff {
+    /// Delay after `failures` consecutive failed deliveries.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+pub struct SinkOptions {
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    pub backoff: Backoff,
+    pub spool: Spool,
+}
+
+enum SinkMessage {
+    Record(Value),
+    Shutdown,
+}
+
+/// Tracing layer forwarding records to the sink workers. Never blocks, records are dropped
+/// and counted if a worker's queue is full.
+pub struct SinkLayer {
+    senders: Vec<SyncSender<SinkMessage>>,
+    dropped: Arc<AtomicU64>,
+}
+
+pub struct SinkHandle {
+    senders: Vec<SyncSender<SinkMessage>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// Starts the sinks enabled in the config, if any.
+pub fn start_from_config(config: &Config) -> Option<(SinkLayer, SinkHandle)> {
+    let webhook = config.webhook.as_ref()?;
+    let options = SinkOptions {
+        batch_size: webhook.batch_size,
+        flush_interval: webhook.flush_interval(),
+        backoff: Backoff { initial: webhook.initial_backoff(), max: webhook.max_backoff() },
+        spool: Spool::new(&webhook.spool_dir(), "webhook", webhook.spool_max_bytes),
+    };
+    Some(start(vec![(Box::new(webhook::WebhookSink::new(webhook)), options)]))
+}
+
+pub fn start(sinks: Vec<(Box<dyn Sink>, SinkOptions)>) -> (SinkLayer, SinkHandle) {
+    let dropped = Arc::new(AtomicU64::new(0));
+    let mut senders = Vec::new();
+    let mut workers = Vec::new();
+    for (sink, options) in sinks {
+        let (sender, receiver) = sync_channel(QUEUE_CAPACITY);
+        let worker = SinkWorker { sink, options, batch: Vec::new(), failures: 0, retry_at: None, dropped: dropped.clone() };
+        senders.push(sender);
+        workers.push(std::thread::spawn(move || worker.run(receiver)));
+    }
+    (SinkLayer { senders: senders.clone(), dropped }, SinkHandle { senders, workers })
+}
+
+impl SinkHandle {
+    /// Flushes what is queued, spooling anything that can't be sent, and stops the workers.
+    pub fn shutdown(self) {
+        for sender in &self.senders {
+            _ = sender.send(SinkMessage::Shutdown);
+        }
+        for worker in self.workers {
+            _ = worker.join();
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SinkLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() > Level::INFO {
+            return;
+        }
+        // Same shape as the line written to cua.log
+        let (record, is_record) = event_json(event);
+        if !is_record {
+            return;
+        }
+        let record = Value::Object(record);
+
+        for sender in &self.senders {
+            if let Err(TrySendError::Full(_)) = sender.try_send(SinkMessage::Record(record.clone())) {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+struct SinkWorker {
+    sink: Box<dyn Sink>,
+    options: SinkOptions,
+    batch: Vec<Value>,
+    failures: u32,
+    retry_at: Option<Instant>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl SinkWorker {
+    fn run(mut self, receiver: Receiver<SinkMessage>) {
+        // Anything spooled by the last run goes out first
+        self.flush();
+        let mut last_flush = Instant::now();
+        loop {
+            let timeout = self.options.flush_interval.saturating_sub(last_flush.elapsed());
+            match receiver.recv_timeout(timeout) {
+                Ok(SinkMessage::Record(record)) => {
+                    self.batch.push(record);
+                    if self.batch.len() >= self.options.batch_size {
+                        self.flush();
+                        last_flush = Instant::now();
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    self.flush();
+                    last_flush = Instant::now();
+                },
+                Ok(SinkMessage::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                    self.flush();
+                    break;
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(action="sink_dropped", "Dropped {} records, the sink queue was full", dropped);
+        }
+
+        let batch = std::mem::take(&mut self.batch);
+        if self.retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+            self.spool(&batch);
+            return;
+        }
+
+        let mut pending = match self.options.spool.load() {
+            Ok(spooled) => spooled,
+            Err(err) => {
+                warn!(action="sink_spool", sink=self.sink.name(), "Unable to read spool - {}", err);
+                Vec::new()
+            }
+        };
+        let spooled = pending.len();
+        pending.extend(batch);
+
+        let mut sent = 0;
+        for chunk in pending.chunks(self.options.batch_size) {
+            if let Err(err) = self.sink.send(chunk) {
+                self.failures += 1;
+                let delay = self.options.backoff.delay(self.failures);
+                self.retry_at = Some(Instant::now() + delay);
+                warn!(action="sink_error", sink=self.sink.name(), "Unable to send {} records, retrying in {:?} - {}", pending.len() - sent, delay, err);
+                break;
+            }
+            sent += chunk.len();
+        }
+        if !pending.is_empty() && sent == pending.len() && self.failures > 0 {
+            info!(action="sink_recovered", sink=self.sink.name(), "Sink recovered after {} failed attempts", self.failures);
+            self.failures = 0;
+            self.retry_at = None;
+        }
+
+        // Keep whatever wasn't sent, rewriting the spool if some of it came from there
+        let remaining = &pending[sent..];
+        if spooled > 0 {
+            match self.options.spool.replace(remaining) {
+                Ok(0) => {},
+                Ok(dropped) => warn!(action="sink_dropped", sink=self.sink.name(), "Dropped {} records, the spool is full", dropped),
+                Err(err) => warn!(action="sink_spool", sink=self.sink.name(), "Unable to update spool - {}", err)
+            }
+        } else {
+            self.spool(remaining);
+        }
+    }
+
+    fn spool(&self, records: &[Value]) {
+        if records.is_empty() {
+            return;
+        }
+        match self.options.spool.append(records) {
+            Ok(0) => {},
+            Ok(dropped) => warn!(action="sink_dropped", sink=self.sink.name(), "Dropped {} records, the spool is full", dropped),
+            Err(err) => warn!(action="sink_spool", sink=self.sink.name(), "Unable to spool {} records - {}", records.len(), err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventIdType;
+    use crate::events::logon::LogonEvent;
+    use crate::records::{self, LogonLogoffRecord};
+    use serde_json::json;
+    use std::sync::Mutex;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone, Default)]
+    struct FakeSink {
+        failures_left: Arc<Mutex<usize>>,
+        batches: Arc<Mutex<Vec<Vec<Value>>>>,
+    }
+
+    impl Sink for FakeSink {
+        fn name(&self) -> &str {
+            "fake"
+        }
+
+        fn send(&mut self, records: &[Value]) -> platform::Result<()> {
+            let mut failures_left = self.failures_left.lock().unwrap();
+            if *failures_left > 0 {
+                *failures_left -= 1;
+                return Err("endpoint unreachable".into());
+            }
+            self.batches.lock().unwrap().push(records.to_vec());
+            Ok(())
+        }
+    }
+
+    fn options(dir: &std::path::Path, batch_size: usize, initial_backoff: Duration) -> SinkOptions {
+        SinkOptions {
+            batch_size,
+            flush_interval: Duration::from_secs(3600),
+            backoff: Backoff { initial: initial_backoff, max: Duration::from_secs(3600) },
+            spool: Spool::new(dir, "fake", 1024 * 1024),
+        }
+    }
+
+    fn send(layer: &SinkLayer, records: impl IntoIterator<Item = Value>) {
+        for record in records {
+            layer.senders[0].send(SinkMessage::Record(record)).unwrap();
+        }
+    }
+
+    fn numbered(range: std::ops::Range<u64>) -> Vec<Value> {
+        range.map(|n| json!({"n": n})).collect()
+    }
+
+    #[test]
+    fn layer_forwards_only_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = FakeSink::default();
+        let (layer, handle) = start(vec![(Box::new(sink.clone()), options(dir.path(), 10, Duration::ZERO))]);
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            records::emit(&LogonLogoffRecord::new(EventIdType::Logon, LogonEvent {
+                target_user_sid: "S-1-5-21-1".to_string(),
+                logon_type: Some(2),
+                ..LogonEvent::default()
+            }));
+            info!(action="watcher_start", watcher="logon_logoff", "logon_logoff watcher started sucessfully");
+            tracing::debug!(action="current_user_info", record="{\"logged_in\":false}", "Not at INFO");
+        });
+        handle.shutdown();
+
+        let batches = sink.batches.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+        let record = &batches[0][0];
+        assert_eq!(batches[0].len(), 1);
+        assert_eq!(record["action"], "logon_logoff_event");
+        assert_eq!(record["user_sid"], "S-1-5-21-1");
+        assert_eq!(record["logon_type"], "2");
+        assert_eq!(record["level"], "INFO");
+        assert_eq!(record["schema_version"], records::SCHEMA_VERSION);
+        assert_eq!(record["target"], "cua::records");
+        assert!(record["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn records_are_sent_in_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = FakeSink::default();
+        let (layer, handle) = start(vec![(Box::new(sink.clone()), options(dir.path(), 2, Duration::ZERO))]);
+        send(&layer, numbered(0..5));
+        handle.shutdown();
+
+        let batches = sink.batches.lock().unwrap();
+        assert_eq!(*batches, vec![numbered(0..2), numbered(2..4), numbered(4..5)]);
+    }
+
+    #[test]
+    fn failed_batches_are_spooled_and_sent_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = FakeSink { failures_left: Arc::new(Mutex::new(1)), ..FakeSink::default() };
+        let (layer, handle) = start(vec![(Box::new(sink.clone()), options(dir.path(), 2, Duration::ZERO))]);
+        send(&layer, numbered(0..4));
+        handle.shutdown();
+
+        assert_eq!(*sink.batches.lock().unwrap(), vec![numbered(0..2), numbered(2..4)]);
+        assert!(Spool::new(dir.path(), "fake", 0).is_empty());
+    }
+
+    #[test]
+    fn records_stay_spooled_during_backoff_and_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = FakeSink { failures_left: Arc::new(Mutex::new(1)), ..FakeSink::default() };
+        let (layer, handle) = start(vec![(Box::new(sink.clone()), options(dir.path(), 2, Duration::from_secs(3600)))]);
+        send(&layer, numbered(0..5));
+        handle.shutdown();
+
+        assert!(sink.batches.lock().unwrap().is_empty());
+        assert_eq!(Spool::new(dir.path(), "fake", 0).load().unwrap(), numbered(0..5));
+
+        // The next run sends the spool before anything new
+        let (layer, handle) = start(vec![(Box::new(sink.clone()), options(dir.path(), 2, Duration::from_secs(3600)))]);
+        send(&layer, numbered(5..6));
+        handle.shutdown();
+
+        assert_eq!(*sink.batches.lock().unwrap(), vec![numbered(0..2), numbered(2..4), numbered(4..5), numbered(5..6)]);
+        assert!(Spool::new(dir.path(), "fake", 0).is_empty());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let backoff = Backoff { initial: Duration::from_secs(1), max: Duration::from_secs(5) };
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(2), Duration::from_secs(2));
+        assert_eq!(backoff.delay(3), Duration::from_secs(4));
+        assert_eq!(backoff.delay(4), Duration::from_secs(5));
+        assert_eq!(backoff.delay(100), Duration::from_secs(5));
+    }
+}
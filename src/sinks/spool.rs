@@ -0,0 +1,134 @@
+//! Records a sink couldn't deliver, kept as JSON lines until the endpoint is reachable again.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use tracing::warn;
+
+use crate::state::{read, write_atomic};
+
+pub struct Spool {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl Spool {
+    pub fn new(dir: &Path, name: &str, max_bytes: u64) -> Self {
+        Self { path: dir.join(format!("{}.jsonl", name)), max_bytes }
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    fn size(&self) -> u64 {
+        std::fs::metadata(&self.path).map(|metadata| metadata.len()).unwrap_or(0)
+    }
+
+    /// Appends records until the spool reaches `max_bytes`, returning how many didn't fit.
+    pub fn append(&self, records: &[Value]) -> io::Result<usize> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let (lines, appended) = self.fit(records, self.size());
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        Ok(records.len() - appended)
+    }
+
+    pub fn load(&self) -> io::Result<Vec<Value>> {
+        let contents = read(&self.path)?.unwrap_or_default();
+        Ok(contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| serde_json::from_str(line)
+                .inspect_err(|err| warn!(action="sink_spool", "Skipping unreadable spooled record - {}", err))
+                .ok())
+            .collect())
+    }
+
+    /// Replaces the spool with the records still to be delivered, as many as fit in
+    /// `max_bytes`. Returns how many didn't fit.
+    pub fn replace(&self, records: &[Value]) -> io::Result<usize> {
+        if records.is_empty() {
+            self.clear()?;
+            return Ok(0);
+        }
+        let (lines, kept) = self.fit(records, 0);
+        write_atomic(&self.path, lines.as_bytes())?;
+        Ok(records.len() - kept)
+    }
+
+    /// The JSON lines for the oldest records that fit after `size` bytes, and how many there are.
+    fn fit(&self, records: &[Value], mut size: u64) -> (String, usize) {
+        let mut lines = String::new();
+        let mut count = 0;
+        for record in records {
+            let line = record.to_string() + "\n";
+            if size + line.len() as u64 > self.max_bytes {
+                break;
+            }
+            size += line.len() as u64;
+            lines.push_str(&line);
+            count += 1;
+        }
+        (lines, count)
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn append_load_and_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::new(dir.path(), "webhook", 1024);
+        assert!(spool.is_empty());
+        assert!(spool.load().unwrap().is_empty());
+
+        spool.append(&[json!({"action": "tenant_info"})]).unwrap();
+        spool.append(&[json!({"action": "current_user_info"})]).unwrap();
+        assert_eq!(spool.load().unwrap(), vec![json!({"action": "tenant_info"}), json!({"action": "current_user_info"})]);
+
+        spool.replace(&[json!({"action": "current_user_info"})]).unwrap();
+        assert_eq!(spool.load().unwrap(), vec![json!({"action": "current_user_info"})]);
+
+        spool.clear().unwrap();
+        assert!(spool.is_empty());
+        spool.clear().unwrap();
+    }
+
+    #[test]
+    fn append_stops_at_max_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = json!({"action": "logon_logoff_event"});
+        let spool = Spool::new(dir.path(), "webhook", (record.to_string().len() as u64 + 1) * 2);
+        assert_eq!(spool.append(&[record.clone(), record.clone(), record.clone()]).unwrap(), 1);
+        assert_eq!(spool.append(std::slice::from_ref(&record)).unwrap(), 1);
+        assert_eq!(spool.load().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn replace_stops_at_max_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let records = (0..3).map(|n| json!({"n": n})).collect::<Vec<Value>>();
+        let spool = Spool::new(dir.path(), "webhook", (records[0].to_string().len() as u64 + 1) * 2);
+        spool.append(&records[..1]).unwrap();
+        assert_eq!(spool.replace(&records).unwrap(), 1);
+        assert_eq!(spool.load().unwrap(), records[..2]);
+        assert_eq!(spool.replace(&[]).unwrap(), 0);
+        assert!(spool.is_empty());
+    }
+}
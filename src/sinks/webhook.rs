@@ -0,0 +1,126 @@
+//! POSTs batches of records to an HTTP endpoint as a JSON array.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use ureq::Agent;
+
+use crate::config::WebhookConfig;
+use crate::platform;
+
+use super::Sink;
+
+pub struct WebhookSink {
+    agent: Agent,
+    url: String,
+    headers: BTreeMap<String, String>,
+}
+
+impl WebhookSink {
+    pub fn new(config: &WebhookConfig) -> Self {
+        let agent_config = Agent::config_builder()
+            .timeout_global(Some(config.timeout()))
+            .http_status_as_error(false)
+            .build();
+        Self {
+            agent: Agent::new_with_config(agent_config),
+            url: config.url.clone(),
+            headers: config.headers.clone(),
+        }
+    }
+}
+
+impl Sink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn send(&mut self, records: &[Value]) -> platform::Result<()> {
+        let body = serde_json::to_string(records)?;
+        let mut request = self.agent.post(&self.url).header("Content-Type", "application/json");
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        let response = request.send(body)?;
+        if !response.status().is_success() {
+            return Err(format!("{} returned {}", self.url, response.status()).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::{self, CurrentUserRecord, TenantInfoRecord};
+    use crate::sinks::{Backoff, SinkOptions, spool::Spool};
+    use crate::test_support::MockHttpServer;
+    use serde_json::json;
+    use std::time::Duration;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn posts_records_as_json_array() {
+        let server = MockHttpServer::start(vec![200]);
+        let config = WebhookConfig {
+            url: server.url("/ingest"),
+            headers: BTreeMap::from([("Authorization".to_string(), "Bearer abc".to_string())]),
+            ..WebhookConfig::default()
+        };
+        let mut sink = WebhookSink::new(&config);
+        sink.send(&[json!({"action": "tenant_info"}), json!({"action": "current_user_info"})]).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].head.starts_with("POST /ingest HTTP/1.1"));
+        assert!(requests[0].header("authorization").is_some_and(|value| value == "Bearer abc"));
+        assert_eq!(requests[0].json(), json!([{"action": "tenant_info"}, {"action": "current_user_info"}]));
+    }
+
+    #[test]
+    fn error_status_is_an_error() {
+        let server = MockHttpServer::start(vec![503]);
+        let mut sink = WebhookSink::new(&WebhookConfig { url: server.url("/ingest"), ..WebhookConfig::default() });
+        assert!(sink.send(&[json!({"action": "tenant_info"})]).is_err());
+    }
+
+    #[test]
+    fn unreachable_endpoint_is_an_error() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ingest", listener.local_addr().unwrap());
+        drop(listener);
+        let mut sink = WebhookSink::new(&WebhookConfig { url, ..WebhookConfig::default() });
+        assert!(sink.send(&[json!({"action": "tenant_info"})]).is_err());
+    }
+
+    #[test]
+    fn worker_retries_failed_batches_against_endpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockHttpServer::start(vec![503]);
+        let sink = WebhookSink::new(&WebhookConfig { url: server.url("/ingest"), ..WebhookConfig::default() });
+        let options = SinkOptions {
+            batch_size: 1,
+            flush_interval: Duration::from_secs(3600),
+            backoff: Backoff { initial: Duration::ZERO, max: Duration::ZERO },
+            spool: Spool::new(dir.path(), "webhook", 1024 * 1024),
+        };
+        let (layer, handle) = crate::sinks::start(vec![(Box::new(sink), options)]);
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            records::emit(&TenantInfoRecord {
+                tenant_id: "338f14b1-cb11-41e8-90ae-b06bc0fdd75a".to_string(),
+                device_id: "af4edcd4-4bb6-4679-8b0e-64c3262a1de4".to_string(),
+                registered_user: "test@jordangomes.com".to_string(),
+            });
+            records::emit(&CurrentUserRecord::from(None));
+        });
+        handle.shutdown();
+
+        let bodies: Vec<Value> = server.requests().iter().map(|request| request.json()).collect();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[0], bodies[1]);
+        assert_eq!(bodies[1][0]["action"], "tenant_info");
+        assert_eq!(bodies[2][0]["action"], "current_user_info");
+    }
+}
@@ -0,0 +1,178 @@
+//! Persists the `EvtSubscribe` bookmark for a watcher so events raised while the service
+//! was stopped are delivered when it starts again. The service writes it from the record
+//! IDs it has processed, so events still queued or held back by a debouncer when it stops
+//! are delivered again rather than skipped.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use quick_xml::escape::escape;
+use tracing::warn;
+
+use crate::events::dedupe::RecordIdTracker;
+
+use super::{read, write_atomic};
+
+pub struct BookmarkStore {
+    path: PathBuf,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StartPosition {
+    /// Resume after the event recorded in the bookmark XML.
+    AfterBookmark(String),
+    /// No bookmark, start with events raised within the window.
+    LookBack(Duration),
+    /// No bookmark and no look-back, only new events.
+    FutureEvents,
+}
+
+impl BookmarkStore {
+    pub fn new(dir: &Path, name: &str) -> Self {
+        Self { path: dir.join(format!("{}.bookmark.xml", name)) }
+    }
+
+    #[cfg(test)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the saved bookmark XML, ignoring files that are unreadable or don't look
+    /// like a bookmark so a bad state file can't stop the watcher starting.
+    pub fn load(&self) -> Option<String> {
+        match read(&self.path) {
+            Ok(Some(bookmark_xml)) if bookmark_xml.trim_start().starts_with("<BookmarkList") => Some(bookmark_xml),
+            Ok(Some(_)) => {
+                warn!(action="bookmark_load", "Ignoring invalid bookmark file {}", self.path.display());
+                None
+            },
+            Ok(None) => None,
+            Err(err) => {
+                warn!(action="bookmark_load", "Unable to read bookmark file {} - {}", self.path.display(), err);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, bookmark_xml: &str) -> io::Result<()> {
+        write_atomic(&self.path, bookmark_xml.as_bytes())
+    }
+
+    pub fn start_position(&self, look_back: Duration) -> StartPosition {
+        start_position(self.load(), look_back)
+    }
+}
+
+pub fn start_position(bookmark_xml: Option<String>, look_back: Duration) -> StartPosition {
+    match bookmark_xml {
+        Some(bookmark_xml) => StartPosition::AfterBookmark(bookmark_xml),
+        None if look_back.is_zero() => StartPosition::FutureEvents,
+        None => StartPosition::LookBack(look_back)
+    }
+}
+
+/// Builds the bookmark XML `EvtRenderBookmark` would give after the highest record ID in
+/// each channel, the most recent one marked current. None before any event has a record ID.
+pub fn bookmark_xml(record_ids: &RecordIdTracker) -> Option<String> {
+    let mut highest = record_ids.highest().filter(|(channel, ..)| !channel.is_empty()).collect::<Vec<_>>();
+    let current = highest.iter().max_by_key(|(_, _, time)| *time)?.0;
+    highest.sort_by_key(|(channel, ..)| *channel);
+    let bookmarks = highest.iter()
+        .map(|(channel, record_id, _)| match *channel == current {
+            true => format!("  <Bookmark Channel='{}' RecordId='{}' IsCurrent='true'/>\r\n", escape(*channel), record_id),
+            false => format!("  <Bookmark Channel='{}' RecordId='{}'/>\r\n", escape(*channel), record_id)
+        })
+        .collect::<String>();
+    Some(format!("<BookmarkList>\r\n{}</BookmarkList>", bookmarks))
+}
+
+/// Restricts an `Event[...]`, `*[...]` or `*` XPath query to events raised within
+/// `look_back`. None for any other query, the window can't be added without parsing it.
+pub fn with_look_back(xpath: &str, look_back: Duration) -> Option<String> {
+    let time_filter = format!("System[TimeCreated[timediff(@SystemTime) <= {}]]", look_back.as_millis());
+    if xpath.trim() == "*" {
+        return Some(format!("*[{}]", time_filter));
+    }
+    for prefix in ["Event[", "*["] {
+        if let Some(inner) = xpath.strip_prefix(prefix).and_then(|rest| rest.strip_suffix(']')) {
+            return Some(format!("{}({}) and {}]", prefix, inner, time_filter));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::event_at;
+
+    const BOOKMARK: &str = "<BookmarkList>\r\n  <Bookmark Channel='Security' RecordId='48211' IsCurrent='true'/>\r\n</BookmarkList>";
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BookmarkStore::new(dir.path(), "logon_logoff");
+        assert_eq!(store.load(), None);
+
+        store.save(BOOKMARK).unwrap();
+        assert_eq!(store.load().as_deref(), Some(BOOKMARK));
+
+        let newer = BOOKMARK.replace("48211", "48230");
+        store.save(&newer).unwrap();
+        assert_eq!(store.load(), Some(newer));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn invalid_bookmark_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BookmarkStore::new(dir.path(), "logon_logoff");
+        std::fs::write(store.path(), "\0\0\0").unwrap();
+        assert_eq!(store.load(), None);
+        assert_eq!(store.start_position(Duration::from_secs(60)), StartPosition::LookBack(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn start_position_prefers_bookmark() {
+        assert_eq!(start_position(Some(BOOKMARK.to_string()), Duration::from_secs(60)), StartPosition::AfterBookmark(BOOKMARK.to_string()));
+        assert_eq!(start_position(None, Duration::from_secs(60)), StartPosition::LookBack(Duration::from_secs(60)));
+        assert_eq!(start_position(None, Duration::ZERO), StartPosition::FutureEvents);
+    }
+
+    #[test]
+    fn bookmark_is_built_from_record_ids() {
+        let mut record_ids = RecordIdTracker::default();
+        assert_eq!(bookmark_xml(&record_ids), None);
+
+        for (channel, record_id, time) in [("Security", 48211, "2026-02-27T05:00:00Z"), ("System", 312, "2026-02-27T04:00:00Z")] {
+            let mut event = event_at(time.parse().unwrap(), 4624, &[]);
+            event.system.event_record_id = Some(record_id);
+            event.system.channel = Some(channel.to_string());
+            record_ids.observe(&event);
+        }
+        assert_eq!(
+            bookmark_xml(&record_ids).as_deref(),
+            Some("<BookmarkList>\r\n  <Bookmark Channel='Security' RecordId='48211' IsCurrent='true'/>\r\n  <Bookmark Channel='System' RecordId='312'/>\r\n</BookmarkList>")
+        );
+    }
+
+    #[test]
+    fn look_back_is_added_to_query() {
+        assert_eq!(
+            with_look_back("Event[System[(EventID='4647')]]", Duration::from_secs(3600)).as_deref(),
+            Some("Event[(System[(EventID='4647')]) and System[TimeCreated[timediff(@SystemTime) <= 3600000]]]")
+        );
+        assert_eq!(
+            with_look_back("*[System[(EventID='4647')]]", Duration::from_secs(1)).as_deref(),
+            Some("*[(System[(EventID='4647')]) and System[TimeCreated[timediff(@SystemTime) <= 1000]]]")
+        );
+        assert_eq!(with_look_back("*", Duration::from_secs(1)).as_deref(), Some("*[System[TimeCreated[timediff(@SystemTime) <= 1000]]]"));
+    }
+
+    #[test]
+    fn look_back_is_not_added_to_other_queries() {
+        assert_eq!(with_look_back("<QueryList/>", Duration::from_secs(1)), None);
+        assert_eq!(with_look_back("Event/System[EventID=4624]", Duration::from_secs(1)), None);
+    }
+}
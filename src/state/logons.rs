@@ -0,0 +1,68 @@
+//! Persists the logons still waiting for their logoff, so a session that spans a restart of
+//! the service is still matched.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::events::correlation::LogonTracker;
+
+use super::{read, write_atomic};
+
+pub struct LogonStore {
+    path: PathBuf,
+}
+
+impl LogonStore {
+    pub fn new(dir: &Path) -> Self {
+        Self { path: dir.join("open_logons.json") }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the saved logons, starting empty when the file is missing or unreadable.
+    pub fn load(&self) -> LogonTracker {
+        match read(&self.path) {
+            Ok(Some(json)) => serde_json::from_str(&json).unwrap_or_else(|err| {
+                warn!(action="logon_state_load", "Ignoring invalid logon state file {} - {}", self.path.display(), err);
+                LogonTracker::default()
+            }),
+            Ok(None) => LogonTracker::default(),
+            Err(err) => {
+                warn!(action="logon_state_load", "Unable to read logon state file {} - {}", self.path.display(), err);
+                LogonTracker::default()
+            }
+        }
+    }
+
+    pub fn save(&self, tracker: &LogonTracker) -> io::Result<()> {
+        write_atomic(&self.path, &serde_json::to_vec(tracker)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventIdType;
+    use crate::events::logon::LogonEvent;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LogonStore::new(dir.path());
+        assert_eq!(store.load(), LogonTracker::default());
+
+        let mut tracker = LogonTracker::default();
+        let logon = LogonEvent { target_user_sid: "S-1-5-21-1".to_string(), target_logon_id: Some(0x4a0b2c), ..LogonEvent::default() };
+        tracker.observe(EventIdType::Logon, "2026-02-27T05:00:00Z".parse().unwrap(), &logon);
+        assert!(tracker.take_changed());
+        store.save(&tracker).unwrap();
+        assert_eq!(store.load(), tracker);
+
+        std::fs::write(store.path(), "{").unwrap();
+        assert_eq!(store.load(), LogonTracker::default());
+    }
+}
@@ -0,0 +1,40 @@
+//! Small files cua keeps between restarts, stored under `%ProgramData%\cua\state`.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub mod bookmark;
+pub mod logons;
+pub mod record_ids;
+pub mod tenants;
+
+pub fn state_dir() -> PathBuf {
+    let program_data = std::env::var_os("ProgramData").unwrap_or("C:\\ProgramData".into());
+    PathBuf::from(program_data).join("cua").join("state")
+}
+
+/// Writes to a temporary file and renames it over `path` so a crash mid-write never
+/// leaves a truncated state file behind.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&temp_path, path)
+}
+
+/// Reads a state file, treating a missing file as no state.
+pub fn read(path: &Path) -> io::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err)
+    }
+}
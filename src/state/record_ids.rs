@@ -0,0 +1,76 @@
+//! Persists the highest record ID each watcher has seen in each channel, so events a
+//! bookmark or look-back replays after a restart are not recorded twice.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::events::dedupe::RecordIdTracker;
+use crate::events::queue::Watcher;
+
+use super::{read, write_atomic};
+
+pub type RecordIds = HashMap<Watcher, RecordIdTracker>;
+
+pub struct RecordIdStore {
+    path: PathBuf,
+}
+
+impl RecordIdStore {
+    pub fn new(dir: &Path) -> Self {
+        Self { path: dir.join("record_ids.json") }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the saved record IDs, starting empty when the file is missing or unreadable.
+    pub fn load(&self) -> RecordIds {
+        match read(&self.path) {
+            Ok(Some(json)) => serde_json::from_str(&json).unwrap_or_else(|err| {
+                warn!(action="record_id_state_load", "Ignoring invalid record ID state file {} - {}", self.path.display(), err);
+                RecordIds::default()
+            }),
+            Ok(None) => RecordIds::default(),
+            Err(err) => {
+                warn!(action="record_id_state_load", "Unable to read record ID state file {} - {}", self.path.display(), err);
+                RecordIds::default()
+            }
+        }
+    }
+
+    pub fn save(&self, record_ids: &RecordIds) -> io::Result<()> {
+        write_atomic(&self.path, &serde_json::to_vec(record_ids)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::dedupe::Seen;
+    use crate::test_support::event_at;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RecordIdStore::new(dir.path());
+        assert_eq!(store.load(), RecordIds::default());
+
+        let mut event = event_at("2026-02-27T05:00:00Z".parse().unwrap(), 4624, &[]);
+        event.system.event_record_id = Some(48211);
+        event.system.channel = Some("Security".to_string());
+        let mut record_ids = RecordIds::default();
+        record_ids.entry(Watcher::LogonLogoff).or_default().observe(&event);
+        store.save(&record_ids).unwrap();
+
+        let mut loaded = store.load();
+        assert_eq!(loaded, record_ids);
+        assert_eq!(loaded.entry(Watcher::LogonLogoff).or_default().observe(&event), Seen::Duplicate);
+
+        std::fs::write(store.path(), "[").unwrap();
+        assert_eq!(store.load(), RecordIds::default());
+    }
+}
@@ -0,0 +1,69 @@
+//! Persists the Entra ID tenants the device was last seen joined to, so a join or leave
+//! while the service was stopped is still written as `tenant_join_changed`.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::platform::EntraJoinInfo;
+
+use super::{read, write_atomic};
+
+pub struct TenantStore {
+    path: PathBuf,
+}
+
+impl TenantStore {
+    pub fn new(dir: &Path) -> Self {
+        Self { path: dir.join("tenants.json") }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the saved tenants, None when they were never saved or the file is unreadable.
+    pub fn load(&self) -> Option<Vec<EntraJoinInfo>> {
+        match read(&self.path) {
+            Ok(Some(json)) => serde_json::from_str(&json)
+                .inspect_err(|err| warn!(action="tenant_state_load", "Ignoring invalid tenant state file {} - {}", self.path.display(), err))
+                .ok(),
+            Ok(None) => None,
+            Err(err) => {
+                warn!(action="tenant_state_load", "Unable to read tenant state file {} - {}", self.path.display(), err);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, tenants: &[EntraJoinInfo]) -> io::Result<()> {
+        write_atomic(&self.path, &serde_json::to_vec(tenants)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TenantStore::new(dir.path());
+        assert_eq!(store.load(), None);
+
+        store.save(&[]).unwrap();
+        assert_eq!(store.load(), Some(Vec::new()));
+
+        let tenants = vec![EntraJoinInfo {
+            tenant_id: "338f14b1-cb11-41e8-90ae-b06bc0fdd75a".to_string(),
+            device_id: "af4edcd4-4bb6-4679-8b0e-64c3262a1de4".to_string(),
+            registered_user: "test@jordangomes.com".to_string(),
+        }];
+        store.save(&tenants).unwrap();
+        assert_eq!(store.load(), Some(tenants));
+
+        std::fs::write(store.path(), "{").unwrap();
+        assert_eq!(store.load(), None);
+    }
+}
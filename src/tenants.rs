@@ -0,0 +1,158 @@
+//! Remembers the Entra ID tenants the device was last seen joined to, so `tenant_info` is
+//! only written when the join state changes or `refresh_interval` has passed rather than
+//! after every logon. The service saves them between restarts, see `state::tenants`.
+
+use std::time::{Duration, Instant};
+
+use crate::platform::EntraJoinInfo;
+
+/// How the tenants changed between two reads, by `tenant_id`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TenantDiff {
+    pub joined: Vec<EntraJoinInfo>,
+    pub left: Vec<EntraJoinInfo>,
+    /// Joined to the same tenant again, as (before, after). The device ID changes when the
+    /// device is registered again.
+    pub rejoined: Vec<(EntraJoinInfo, EntraJoinInfo)>,
+}
+
+impl TenantDiff {
+    pub fn is_empty(&self) -> bool {
+        self.joined.is_empty() && self.left.is_empty() && self.rejoined.is_empty()
+    }
+}
+
+pub fn diff(before: &[EntraJoinInfo], after: &[EntraJoinInfo]) -> TenantDiff {
+    let find = |tenants: &[EntraJoinInfo], tenant_id: &str| tenants.iter().find(|tenant| tenant.tenant_id == tenant_id).cloned();
+    let mut diff = TenantDiff::default();
+    for tenant in after {
+        match find(before, &tenant.tenant_id) {
+            None => diff.joined.push(tenant.clone()),
+            Some(previous) if previous != *tenant => diff.rejoined.push((previous, tenant.clone())),
+            Some(_) => {}
+        }
+    }
+    diff.left = before.iter().filter(|tenant| find(after, &tenant.tenant_id).is_none()).cloned().collect();
+    diff
+}
+
+/// What to record after reading the tenants.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TenantUpdate {
+    /// Whether to write `tenant_info` for the tenants read.
+    pub emit: bool,
+    /// The change since the last read, None the first time and when nothing changed.
+    pub changed: Option<TenantDiff>,
+}
+
+#[derive(Debug)]
+pub struct TenantCache {
+    /// None only writes `tenant_info` when the tenants change.
+    refresh_interval: Option<Duration>,
+    last: Option<Vec<EntraJoinInfo>>,
+    last_emitted: Option<Instant>,
+    /// Whether `last` changed since `take_changed`.
+    changed: bool,
+}
+
+impl TenantCache {
+    pub fn new(refresh_interval: Option<Duration>) -> Self {
+        Self { refresh_interval, last: None, last_emitted: None, changed: false }
+    }
+
+    /// Starts from the tenants saved before a restart, so the first read is diffed against them.
+    pub fn with_last(mut self, last: Option<Vec<EntraJoinInfo>>) -> Self {
+        self.last = last;
+        self
+    }
+
+    /// The tenants from the last read.
+    pub fn last(&self) -> Option<&[EntraJoinInfo]> {
+        self.last.as_deref()
+    }
+
+    /// Whether the tenants read differ from the ones before, since the last call.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn update(&mut self, tenants: &[EntraJoinInfo], now: Instant) -> TenantUpdate {
+        let changed = self.last.as_ref().map(|last| diff(last, tenants)).filter(|diff| !diff.is_empty());
+        let refresh_due = match (self.last_emitted, self.refresh_interval) {
+            (None, _) => true,
+            (Some(last_emitted), Some(refresh_interval)) => now.duration_since(last_emitted) >= refresh_interval,
+            (Some(_), None) => false
+        };
+        let emit = changed.is_some() || refresh_due;
+        if emit {
+            self.last_emitted = Some(now);
+        }
+        if self.last.as_deref() != Some(tenants) {
+            self.changed = true;
+            self.last = Some(tenants.to_vec());
+        }
+        TenantUpdate { emit, changed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenant(tenant_id: &str, device_id: &str) -> EntraJoinInfo {
+        EntraJoinInfo { tenant_id: tenant_id.to_string(), device_id: device_id.to_string(), registered_user: "test@jordangomes.com".to_string() }
+    }
+
+    #[test]
+    fn diff_finds_joined_left_and_rejoined() {
+        let before = vec![tenant("contoso", "device-1"), tenant("fabrikam", "device-2")];
+        let after = vec![tenant("contoso", "device-3"), tenant("northwind", "device-4")];
+
+        let changes = diff(&before, &after);
+        assert_eq!(changes.joined, vec![tenant("northwind", "device-4")]);
+        assert_eq!(changes.left, vec![tenant("fabrikam", "device-2")]);
+        assert_eq!(changes.rejoined, vec![(tenant("contoso", "device-1"), tenant("contoso", "device-3"))]);
+        assert!(diff(&after, &after).is_empty());
+        assert!(diff(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn only_changes_and_refreshes_are_emitted() {
+        let start = Instant::now();
+        let joined = vec![tenant("contoso", "device-1")];
+        let mut cache = TenantCache::new(Some(Duration::from_secs(3600)));
+
+        assert_eq!(cache.update(&joined, start), TenantUpdate { emit: true, changed: None });
+        assert_eq!(cache.update(&joined, start + Duration::from_secs(60)), TenantUpdate { emit: false, changed: None });
+        assert!(cache.update(&joined, start + Duration::from_secs(3600)).emit);
+        assert!(!cache.update(&joined, start + Duration::from_secs(3660)).emit);
+
+        let left = cache.update(&[], start + Duration::from_secs(3700));
+        assert!(left.emit);
+        assert_eq!(left.changed.unwrap().left, joined);
+    }
+
+    #[test]
+    fn saved_tenants_are_diffed_on_the_first_read() {
+        let start = Instant::now();
+        let mut cache = TenantCache::new(None).with_last(Some(vec![tenant("contoso", "device-1")]));
+
+        let update = cache.update(&[tenant("contoso", "device-2")], start);
+        assert!(update.emit);
+        assert_eq!(update.changed.unwrap().rejoined.len(), 1);
+        assert!(cache.take_changed());
+        assert_eq!(cache.last(), Some(&[tenant("contoso", "device-2")][..]));
+
+        assert_eq!(cache.update(&[tenant("contoso", "device-2")], start), TenantUpdate { emit: false, changed: None });
+        assert!(!cache.take_changed());
+    }
+
+    #[test]
+    fn no_refresh_interval_only_emits_changes() {
+        let start = Instant::now();
+        let mut cache = TenantCache::new(None);
+        assert!(cache.update(&[], start).emit);
+        assert!(!cache.update(&[], start + Duration::from_secs(86400 * 30)).emit);
+        assert_eq!(cache.update(&[tenant("contoso", "device-1")], start + Duration::from_secs(86400 * 31)).changed.unwrap().joined.len(), 1);
+    }
+}
@@ -0,0 +1,151 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::events::{Event, EventData, EventDataField, System, TimeCreated};
+use crate::logging::json_subscriber;
+
+#[derive(Clone, Default)]
+struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CaptureWriter {
+    type Writer = CaptureWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Runs `f` with a JSON subscriber matching the one in `run_service` and returns
+/// every line it wrote.
+pub fn capture_records<F: FnOnce()>(f: F) -> Vec<Value> {
+    let writer = CaptureWriter::default();
+    tracing::subscriber::with_default(json_subscriber(writer.clone()), f);
+
+    let output = writer.0.lock().unwrap().clone();
+    String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+pub fn records_with_action<'a>(records: &'a [Value], action: &str) -> Vec<&'a Value> {
+    records.iter().filter(|record| record["action"] == action).collect()
+}
+
+pub fn event(event_id: u32, data: &[(&str, &str)]) -> Event {
+    Event {
+        system: System { event_id, ..System::default() },
+        event_data: Some(EventData {
+            data: data
+                .iter()
+                .map(|(name, value)| EventDataField { name: name.to_string(), value: value.to_string() })
+                .collect(),
+        }),
+        user_data: None,
+    }
+}
+
+pub fn event_at(time: DateTime<Utc>, event_id: u32, data: &[(&str, &str)]) -> Event {
+    let mut event = event(event_id, data);
+    event.system.time_created = Some(TimeCreated { system_time: time });
+    event
+}
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub head: String,
+    pub body: String,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().skip(1).find_map(|line| {
+            let (header, value) = line.split_once(':')?;
+            header.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+/// Minimal HTTP/1.1 server on localhost. Answers each request with the next status in
+/// `statuses`, then 200 once they run out, and records what it was sent.
+pub struct MockHttpServer {
+    address: std::net::SocketAddr,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockHttpServer {
+    pub fn start(statuses: Vec<u16>) -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            let mut statuses = statuses.into_iter();
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let Some(request) = read_request(&mut stream) else { continue };
+                recorded.lock().unwrap().push(request);
+                let status = statuses.next().unwrap_or(200);
+                _ = write!(stream, "HTTP/1.1 {} Mock\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+            }
+        });
+        Self { address, requests }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.address, path)
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: &mut std::net::TcpStream) -> Option<MockRequest> {
+    use std::io::Read;
+
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let head_end = loop {
+        let read = stream.read(&mut buffer).ok()?;
+        if read == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buffer[..read]);
+        if let Some(position) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position;
+        }
+    };
+    let head = String::from_utf8_lossy(&data[..head_end]).to_string();
+    let content_length = head.lines()
+        .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|value| value.trim().parse::<usize>().unwrap_or(0)))
+        .unwrap_or(0);
+    let mut body = data[head_end + 4..].to_vec();
+    while body.len() < content_length {
+        let read = stream.read(&mut buffer).ok()?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&buffer[..read]);
+    }
+    Some(MockRequest { head, body: String::from_utf8_lossy(&body).to_string() })
+}
@@ -0,0 +1,33 @@
+use windows::core::{Error, GUID, Result};
+use windows::Win32::Security::Authentication::Identity::{AuditFree, AuditQuerySystemPolicy, AUDIT_POLICY_INFORMATION, POLICY_AUDIT_EVENT_FAILURE, POLICY_AUDIT_EVENT_SUCCESS};
+
+use crate::platform::{self, AuditPolicyProvider, AuditSetting};
+
+pub struct WindowsAuditPolicyProvider;
+
+impl AuditPolicyProvider for WindowsAuditPolicyProvider {
+    fn query_audit_policy(&self, subcategory_guids: &[&str]) -> platform::Result<Vec<AuditSetting>> {
+        Ok(query_audit_policy(subcategory_guids)?)
+    }
+}
+
+/// Needs `SeSecurityPrivilege`, which the service has running as LocalSystem.
+pub fn query_audit_policy(subcategory_guids: &[&str]) -> Result<Vec<AuditSetting>> {
+    let guids = subcategory_guids.iter().map(|guid| GUID::try_from(*guid)).collect::<Result<Vec<GUID>>>()?;
+    let mut policy: *mut AUDIT_POLICY_INFORMATION = std::ptr::null_mut();
+    if !unsafe { AuditQuerySystemPolicy(&guids, &mut policy) } {
+        return Err(Error::from_thread());
+    }
+
+    // One entry for each GUID, in the order they were asked for
+    let entries = unsafe { std::slice::from_raw_parts(policy, guids.len()) };
+    let settings = entries
+        .iter()
+        .map(|entry| AuditSetting {
+            success: entry.AuditingInformation & POLICY_AUDIT_EVENT_SUCCESS as u32 != 0,
+            failure: entry.AuditingInformation & POLICY_AUDIT_EVENT_FAILURE as u32 != 0,
+        })
+        .collect();
+    unsafe { AuditFree(policy as *const _) };
+    Ok(settings)
+}
@@ -5,11 +5,14 @@ use windows_registry::*;
 use x509_certificate::{X509Certificate};
 use bcder::Oid;
 
-#[derive(Debug)]
-pub struct EntraJoinInfo {
-    pub tenant_id: String,
-    pub device_id: String,
-    pub registered_user: String,
+use crate::platform::{self, EntraJoinInfo, JoinInfoProvider};
+
+pub struct WindowsJoinInfoProvider;
+
+impl JoinInfoProvider for WindowsJoinInfoProvider {
+    fn get_entra_join_info(&self) -> platform::Result<Vec<EntraJoinInfo>> {
+        Ok(get_entra_join_info()?)
+    }
 }
 
 const CERT_REG_PATH: &str = "SOFTWARE\\Microsoft\\SystemCertificates\\MY\\Certificates";
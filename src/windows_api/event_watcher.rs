@@ -1,116 +1,177 @@
 use quick_xml::DeError;
-use tracing::error;
+use tracing::{debug, error, info, warn};
+use std::fmt;
 use std::iter;
 use std::os::raw::c_void;
-use std::sync::Mutex;
-use std::time::{Duration, Instant};
-use windows::core::{Error, HRESULT, PCWSTR, Result, w};
-use windows::Win32::System::EventLog::{EvtSubscribe, EvtRender, EVT_SUBSCRIBE_CALLBACK, EVT_SUBSCRIBE_NOTIFY_ACTION, EVT_HANDLE, EvtSubscribeToFutureEvents, EvtSubscribeActionError, EvtSubscribeActionDeliver, EvtRenderEventXml};
-use windows::Win32::Foundation::{HANDLE, ERROR_UNHANDLED_EXCEPTION, ERROR_XML_PARSE_ERROR};
-use serde::Deserialize;
-
-#[derive(Debug, Deserialize)]
-pub struct Event {
-    #[serde(rename = "System")]
-    pub system: System,
-
-    #[serde(rename = "EventData")]
-    pub event_data: Option<EventData>,
-}
+use std::sync::Arc;
+use windows::core::{Error, HRESULT, PCWSTR, Result};
+use windows::Win32::System::EventLog::{EvtClose, EvtSubscribe, EvtRender, EvtCreateBookmark, EVT_SUBSCRIBE_CALLBACK, EVT_SUBSCRIBE_NOTIFY_ACTION, EVT_HANDLE, EVT_RENDER_FLAGS, EvtSubscribeToFutureEvents, EvtSubscribeStartAfterBookmark, EvtSubscribeStartAtOldestRecord, EvtSubscribeActionError, EvtSubscribeActionDeliver, EvtRenderEventXml};
+use windows::Win32::Foundation::{HANDLE, ERROR_INSUFFICIENT_BUFFER, ERROR_UNHANDLED_EXCEPTION, ERROR_XML_PARSE_ERROR};
 
-#[derive(Debug, Deserialize)]
-pub struct System {
-    #[serde(rename = "EventID")]
-    pub event_id: u32,
-}
+use crate::events::Event;
+use crate::events::debounce::DebouncedCallback;
+use crate::platform::{self, EventCallback, EventSource, Subscription, WatcherHandle, utf16};
+use crate::state::{self, bookmark::{BookmarkStore, StartPosition, start_position}};
 
-#[derive(Debug, Deserialize)]
-pub struct EventData {
-    #[serde(rename = "Data")]
-    pub data: Vec<EventDataField>,
-}
+/// Events are a few KB, anything bigger than this is not a logon event worth rendering.
+const MAX_RENDER_BYTES: u32 = 4 * 1024 * 1024;
 
+/// Why an event couldn't be rendered.
 #[derive(Debug)]
-pub enum EventIdType {
-    Logon,
-    Logoff,
-    LogoffInteractive,
-    Unknown
+enum RenderError {
+    /// Bigger than `MAX_RENDER_BYTES`, the event is skipped rather than truncated.
+    TooLarge { size: u32 },
+    Windows(Error),
 }
 
-impl std::fmt::Display for EventIdType {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::TooLarge { size } => write!(f, "Event is {} bytes, more than the {} byte limit", size, MAX_RENDER_BYTES),
+            RenderError::Windows(err) => write!(f, "{}", err.message())
+        }
     }
 }
 
-impl System {
-    pub fn get_event_id_type(&self) -> EventIdType {
-        match self.event_id {
-            4624 => EventIdType::Logon,
-            4634 => EventIdType::Logoff,
-            4647 => EventIdType::LogoffInteractive,
-            _ => EventIdType::Unknown
-        }
-    }
+impl std::error::Error for RenderError {}
+
+struct EventSubscriptionContext {
+    name: String,
+    callback: Arc<DebouncedCallback>,
+    /// Where the subscription started, the service saves the bookmark as it processes events.
+    bookmark: EVT_HANDLE,
+    /// Reused by every `EvtRender` call for this subscription.
+    render_buffer: Vec<u16>,
 }
 
-impl EventData {
-    pub fn get_value(&self, field_name: &str) -> Option<String> {
-        self.data
-            .iter()
-            .find(|field| field.name == field_name)
-            .map(|field| field.value.clone())
+pub struct WindowsEventSource;
+
+impl EventSource for WindowsEventSource {
+    fn subscribe(&self, subscription: Subscription, callback: EventCallback) -> platform::Result<WatcherHandle> {
+        Ok(Box::new(register_event_watcher(subscription, callback)?))
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct EventDataField {
-    #[serde(rename = "@Name")]
+/// Owns an `EvtSubscribe` subscription and the context its callbacks use.
+pub struct EventWatcherHandle {
     name: String,
-
-    #[serde(rename = "$text")]
-    value: String,
+    subscription: EVT_HANDLE,
+    ctx: *mut EventSubscriptionContext,
+    /// Also held by the context, the last one dropped stops the debounce thread and delivers
+    /// the events it was holding back.
+    callback: Arc<DebouncedCallback>,
 }
 
-type UserCallback = Box<dyn FnMut(Event) -> ()>;
-
-struct EventSubscriptionContext {
-    callback: UserCallback,
-    debounce: Option<Duration>,
-    last_call: Mutex<Option<Instant>>,
+// The context is only touched by the subscription's callbacks until the handle frees it
+unsafe impl Send for EventWatcherHandle {}
+
+impl Drop for EventWatcherHandle {
+    fn drop(&mut self) {
+        // Closing the subscription stops new callbacks and waits for one in progress to
+        // return, after that nothing else holds the context
+        if let Err(err) = unsafe { EvtClose(self.subscription) } {
+            // The subscription may still call back into the context, so it is leaked rather
+            // than freed along with its bookmark and debounce thread. The events held back
+            // so far are delivered now so the service still records them
+            error!(action="event_watcher_stop", "Unable to close {} subscription, leaving it running - {}", self.name, err.message());
+            self.callback.flush_all();
+            return;
+        }
+        // The handle's callback is dropped after this, which stops the debounce thread and
+        // delivers the events it was holding back. The service drains its queue after the
+        // watchers are dropped so they are still recorded
+        let EventSubscriptionContext { bookmark, .. } = *unsafe { Box::from_raw(self.ctx) };
+        if let Err(err) = unsafe { EvtClose(bookmark) } {
+            warn!(action="event_watcher_stop", "Unable to close {} bookmark - {}", self.name, err.message());
+        }
+        info!(action="event_watcher_stop", "Stopped {} watcher", self.name);
+    }
 }
 
-pub fn register_event_watcher(xpath: &str, debounce: Option<Duration>, callback: Box<dyn FnMut(Event) -> ()>) -> Result<()> {
+pub fn register_event_watcher(subscription: Subscription, callback: EventCallback) -> Result<EventWatcherHandle> {
     let trampoline_callback: EVT_SUBSCRIBE_CALLBACK = Some(handle_windows_event);
     let session = Some(EVT_HANDLE(0));
     let signal_event = std::ptr::null_mut();
 
-    let bookmark = Some(EVT_HANDLE(0));
+    // Resume from the saved bookmark, otherwise read back over the look-back window
+    let bookmark_store = BookmarkStore::new(&state::state_dir(), &subscription.name);
+    let mut position = bookmark_store.start_position(subscription.look_back);
+    let saved_bookmark = match &position {
+        StartPosition::AfterBookmark(bookmark_xml) => {
+            let bookmark_xml_vec = bookmark_xml.encode_utf16().chain(iter::once(0u16)).collect::<Vec<u16>>();
+            unsafe { EvtCreateBookmark(PCWSTR(bookmark_xml_vec.as_ptr())) }
+                .inspect_err(|err| warn!(action="bookmark_load", "Unable to open bookmark for {} - {}", subscription.name, err.message()))
+                .ok()
+        },
+        _ => None
+    };
+    let bookmark = match saved_bookmark {
+        Some(bookmark) => bookmark,
+        None => {
+            position = start_position(None, subscription.look_back);
+            unsafe { EvtCreateBookmark(PCWSTR::null())? }
+        }
+    };
+    let (query, subscribe_bookmark, flags) = match &position {
+        StartPosition::AfterBookmark(_) => (subscription.query.clone(), Some(bookmark), EvtSubscribeStartAfterBookmark.0),
+        StartPosition::LookBack(look_back) => match subscription.query.with_look_back(*look_back) {
+            Some(query) => (query, None, EvtSubscribeStartAtOldestRecord.0),
+            None => {
+                // Starting at the oldest record without the window would replay the whole log
+                warn!(action="bookmark_look_back", "Unable to add look-back window to the {} query, only reading new events", subscription.name);
+                position = StartPosition::FutureEvents;
+                (subscription.query.clone(), None, EvtSubscribeToFutureEvents.0)
+            }
+        },
+        StartPosition::FutureEvents => (subscription.query.clone(), None, EvtSubscribeToFutureEvents.0)
+    };
+    info!(action="bookmark_load", "Starting {} watcher from {:?}", subscription.name, position);
+
+    let callback = match DebouncedCallback::new(&subscription.name, subscription.debounce, subscription.debounce_strategy, callback) {
+        Ok(callback) => Arc::new(callback),
+        Err(err) => {
+            _ = unsafe { EvtClose(bookmark) };
+            return Err(Error::new(HRESULT(ERROR_UNHANDLED_EXCEPTION.0 as i32), format!("Unable to start debounce thread - {}", err)));
+        }
+    };
 
     let ctx = Box::new(EventSubscriptionContext {
-        callback: callback,
-        debounce,
-        last_call: Mutex::new(None),
+        name: subscription.name.clone(),
+        callback: callback.clone(),
+        bookmark,
+        render_buffer: Vec::new(),
     });
-    let ctx_ptr = Box::into_raw(ctx) as *mut c_void;
-
-    let channel_path = w!("Security");
+    let ctx = Box::into_raw(ctx);
+    let ctx_ptr = ctx as *mut c_void;
 
-    let query_string_vec = xpath.encode_utf16().chain(iter::once(0u16)).collect::<Vec<u16>>();
+    // A structured query names its own channels, so no channel path is passed
+    let query_string_vec = query.to_string().encode_utf16().chain(iter::once(0u16)).collect::<Vec<u16>>();
     let query = PCWSTR(query_string_vec.as_ptr());
 
-    let event_handle = unsafe { EvtSubscribe(session, Some(HANDLE(signal_event)), PCWSTR(channel_path.as_ptr()), query, bookmark, Some(ctx_ptr), trampoline_callback, EvtSubscribeToFutureEvents.0) };
+    let event_handle = unsafe { EvtSubscribe(session, Some(HANDLE(signal_event)), PCWSTR::null(), query, subscribe_bookmark, Some(ctx_ptr), trampoline_callback, flags) };
     match event_handle {
-        Err(err) => { Err(err) },
-        _ => Ok(())
+        Ok(subscription_handle) => Ok(EventWatcherHandle { name: subscription.name, subscription: subscription_handle, ctx, callback }),
+        Err(err) => {
+            // No callbacks were registered, so the context can be freed straight away
+            let ctx = unsafe { Box::from_raw(ctx) };
+            _ = unsafe { EvtClose(ctx.bookmark) };
+            Err(err)
+        }
     }
 }
 
 unsafe extern "system" fn handle_windows_event(action: EVT_SUBSCRIBE_NOTIFY_ACTION, context: *const c_void, event: EVT_HANDLE) -> u32 {
+    let ctx: &mut EventSubscriptionContext = unsafe{ &mut *(context as *mut EventSubscriptionContext) };
     let event_string_result: Result<String> = match action {
         EvtSubscribeActionError => Err(Error::new(HRESULT(event.0 as i32), "Event subscribe action error")),
-        EvtSubscribeActionDeliver => get_event_xml_from_handle(event),
+        EvtSubscribeActionDeliver => match get_event_xml_from_handle(event, &mut ctx.render_buffer) {
+            Ok(event_xml) => Ok(event_xml),
+            Err(err @ RenderError::TooLarge { .. }) => {
+                warn!(action="event_too_large", "Skipping {} event - {}", ctx.name, err);
+                return 0;
+            },
+            Err(RenderError::Windows(err)) => Err(err)
+        },
         _ => Err(Error::new(HRESULT(ERROR_UNHANDLED_EXCEPTION.0 as i32), format!("Unhandled event subscribe action {:?}", action)))
     };
 
@@ -127,46 +188,36 @@ unsafe extern "system" fn handle_windows_event(action: EVT_SUBSCRIBE_NOTIFY_ACTI
 
     match event_result {
         Ok(event) => {
-            let ctx: &mut EventSubscriptionContext = unsafe{ &mut *(context as *mut EventSubscriptionContext) };
-
-            match ctx.debounce {
-                None => {
-                    // No debouncing — call immediately
-                    (ctx.callback)(event)
-                }
-                Some(duration) => {
-                    let now = Instant::now();
-                    let mut last_call = ctx.last_call.lock().unwrap();
-
-                    let should_call = match *last_call {
-                        None => true, // first event
-                        Some(last) => now.duration_since(last) >= duration,
-                    };
-
-                    if should_call {
-                        *last_call = Some(now);
-                        (ctx.callback)(event)
-                    }
-                }
-            }
-                
+            debug!(name="event_watcher", "{} received event {}", ctx.name, event.system);
+            ctx.callback.push(event);
         },
         Err(err) => error!(name="event_watcher", "{}: {:?}", err.message(), err.code())
     };
     0
 }
 
-fn get_event_xml_from_handle(event: EVT_HANDLE) -> Result<String> {
-    const BUFFER_SIZE: usize = 65_000;
-    // Windows uses UTF16 for their strings which means that their strings are 16 bytes wide
-    // instead of the normal 8 for rust's UTF8 strings
-    let mut buffer: [u16; BUFFER_SIZE] = [0; BUFFER_SIZE];
-    let buffer_ptr = buffer.as_mut_ptr() as *mut c_void;
-    let mut property_value_buffer_used:u32 = 0;
-    let mut property_count:u32 = 0;
-    let buffer_opt = Some(buffer_ptr);
-
-    // Call Event Render
-    unsafe { EvtRender(Some(EVT_HANDLE(0)), event, EvtRenderEventXml.0,BUFFER_SIZE as u32, buffer_opt, &mut property_value_buffer_used, &mut property_count)? };
-    Ok(String::from_utf16_lossy(&buffer).trim_matches(char::from(0)).to_string())
-}
\ No newline at end of file
+fn get_event_xml_from_handle(event: EVT_HANDLE, buffer: &mut Vec<u16>) -> std::result::Result<String, RenderError> {
+    render_xml(event, EvtRenderEventXml, buffer)
+}
+
+/// Renders into `buffer`, growing it to the size `EvtRender` asks for and trying again.
+/// The buffer is kept for the next event so it only grows for the largest one seen.
+fn render_xml(handle: EVT_HANDLE, flags: EVT_RENDER_FLAGS, buffer: &mut Vec<u16>) -> std::result::Result<String, RenderError> {
+    let mut buffer_used: u32 = 0;
+    let mut property_count: u32 = 0;
+    loop {
+        // Sizes are in bytes, Windows strings are UTF-16 so two bytes a character
+        let buffer_size = (buffer.len() * size_of::<u16>()) as u32;
+        let buffer_ptr = (!buffer.is_empty()).then_some(buffer.as_mut_ptr() as *mut c_void);
+        match unsafe { EvtRender(Some(EVT_HANDLE(0)), handle, flags.0, buffer_size, buffer_ptr, &mut buffer_used, &mut property_count) } {
+            Ok(()) => return Ok(utf16::to_string(&buffer[..(buffer_used as usize / size_of::<u16>()).min(buffer.len())])),
+            Err(err) if err.code() == ERROR_INSUFFICIENT_BUFFER.to_hresult() && buffer_used > buffer_size => {
+                if buffer_used > MAX_RENDER_BYTES {
+                    return Err(RenderError::TooLarge { size: buffer_used });
+                }
+                buffer.resize((buffer_used as usize).div_ceil(size_of::<u16>()), 0);
+            },
+            Err(err) => return Err(RenderError::Windows(err))
+        }
+    }
+}
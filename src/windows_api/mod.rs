@@ -1,42 +1,20 @@
-use tracing::{error, info};
+use std::sync::Arc;
 
+use crate::platform::Platform;
+use crate::state;
+
+pub mod audit_policy;
 pub mod device_info;
 pub mod user_info;
 pub mod event_watcher;
 
 
-pub fn collect_logs() {
-    let device_info = device_info::get_entra_join_info();
-    match device_info {
-        Err(error) => {
-            error!(action = "tenant_info", "Errror retrieving entra join info - {}", error.message());
-        },
-        Ok(results) => {
-            for result in results {
-                info!(action = "tenant_info", tenant_id = result.tenant_id, device_id = result.device_id, registered_user = result.registered_user);
-            }
-        }
+pub fn platform() -> Platform {
+    Platform {
+        events: Box::new(event_watcher::WindowsEventSource),
+        sessions: Arc::new(user_info::WindowsSessionProvider),
+        join_info: Arc::new(device_info::WindowsJoinInfoProvider),
+        audit_policy: Arc::new(audit_policy::WindowsAuditPolicyProvider),
+        state_dir: state::state_dir(),
     }
-
-    match user_info::get_user_info() {
-        Ok(Some(current_user_info)) => {
-            match current_user_info.azure_ad_object_id {
-                Some(azure_ad_object_id) => info!(
-                    action = "current_user_info", 
-                    user_sid = current_user_info.sid, 
-                    username = current_user_info.username, 
-                    user_type = current_user_info.user_type, 
-                    azure_ad_object_id = azure_ad_object_id),
-                None => info!(
-                    action = "current_user_info", 
-                    user_sid = current_user_info.sid, 
-                    username = current_user_info.username, 
-                    user_type = current_user_info.user_type)
-            };
-        },
-        Ok(None) => { info!(action = "current_user_info", "No user currently logged in"); }
-        Err(err) => {
-            error!(action = "current_user_info", "Unable to retrieve user info: {}", err);
-        }
-    };
-}
\ No newline at end of file
+}
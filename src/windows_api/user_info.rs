@@ -1,7 +1,8 @@
 use std::ffi::{OsString};
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::os::windows::ffi::OsStringExt;
 use std::slice::{self, from_raw_parts};
-use tracing::{debug};
+use tracing::{debug, warn};
 use uuid::Uuid;
 
 use windows::core::{PCWSTR, PWSTR, PSTR};
@@ -11,7 +12,15 @@ use windows::Win32::Globalization::lstrlenW;
 use windows::Win32::Security::{ImpersonateLoggedOnUser, RevertToSelf, GetTokenInformation, TokenUser, TOKEN_USER};
 use windows::Win32::Security::Authorization::ConvertSidToStringSidW;
 use windows::Win32::Security::Authentication::Identity::{GetUserNameExW, NameUserPrincipal, NameSamCompatible};
-use windows::Win32::System::RemoteDesktop::{WTS_CONNECTSTATE_CLASS, WTS_CURRENT_SERVER_HANDLE, WTSConnectState, WTSActive, WTSDisconnected, WTSGetActiveConsoleSessionId, WTSQuerySessionInformationA, WTSQueryUserToken};
+use windows::Win32::System::RemoteDesktop::{WTS_CLIENT_ADDRESS, WTS_CONNECTSTATE_CLASS, WTS_CURRENT_SERVER_HANDLE, WTS_INFO_CLASS, WTS_SESSION_INFOW, WTSConnectState, WTSActive, WTSConnected, WTSConnectQuery, WTSShadow, WTSDisconnected, WTSIdle, WTSListen, WTSReset, WTSDown, WTSInit, WTSClientAddress, WTSClientName, WTSUserName, WTSEnumerateSessionsW, WTSFreeMemory, WTSGetActiveConsoleSessionId, WTSQuerySessionInformationA, WTSQuerySessionInformationW, WTSQueryUserToken};
+
+use windows_service::service::{SessionChangeParam, SessionChangeReason};
+
+use crate::platform::{self, ConnectState, CurrentUserInfo, SessionChange, SessionChangeType, SessionInfo, SessionProvider};
+
+// WTS_CLIENT_ADDRESS address families
+const AF_INET: u32 = 2;
+const AF_INET6: u32 = 23;
 
 struct LocalHeapString {
     inner: PWSTR,
@@ -114,14 +123,19 @@ fn convert_azure_ad_sid_to_object_id(sid: &str) -> Option<String> {
     Uuid::from_slice(&bytes).ok().map(|uuid| uuid.to_string())
 }
 
-pub struct CurrentUserInfo {
-    pub sid: String,
-    pub username: String,
-    pub user_type: String, // Added field for user type
-    pub azure_ad_object_id: Option<String>, // Added optional field for Azure AD Object ID
+pub struct WindowsSessionProvider;
+
+impl SessionProvider for WindowsSessionProvider {
+    fn get_user_info(&self) -> platform::Result<Option<CurrentUserInfo>> {
+        get_user_info()
+    }
+
+    fn list_sessions(&self) -> platform::Result<Vec<SessionInfo>> {
+        list_sessions()
+    }
 }
 
-pub fn get_user_info() -> Result<Option<CurrentUserInfo>, Box<dyn std::error::Error>> {
+pub fn get_user_info() -> platform::Result<Option<CurrentUserInfo>> {
     // Check if there is an active console session
     let session_id = unsafe { WTSGetActiveConsoleSessionId() };
     if session_id == 0xFFFFFFFF {
@@ -149,7 +163,11 @@ pub fn get_user_info() -> Result<Option<CurrentUserInfo>, Box<dyn std::error::Er
         Err(e) => return Err(format!("Unable to get TSession Information for session_id {} - {}", session_id, e.message()).into())
     };
 
-    // Query the active console session for a user token
+    get_session_user(session_id).map(Some)
+}
+
+fn get_session_user(session_id: u32) -> platform::Result<CurrentUserInfo> {
+    // Query the session for a user token
     let mut h_token: HANDLE = HANDLE::default();
     match unsafe { WTSQueryUserToken(session_id, &mut h_token) } {
         Ok(_) => {},
@@ -206,10 +224,119 @@ pub fn get_user_info() -> Result<Option<CurrentUserInfo>, Box<dyn std::error::Er
     };
 
 
-    Ok(Some(CurrentUserInfo {
+    Ok(CurrentUserInfo {
         sid: user_sid,
         username: username_str,
         user_type,
         azure_ad_object_id, // Include the Azure AD Object ID if available
-    }))
-}
\ No newline at end of file
+    })
+}
+
+pub fn list_sessions() -> platform::Result<Vec<SessionInfo>> {
+    let mut session_info: *mut WTS_SESSION_INFOW = std::ptr::null_mut();
+    let mut count: u32 = 0;
+    if let Err(e) = unsafe { WTSEnumerateSessionsW(Some(WTS_CURRENT_SERVER_HANDLE), 0, 1, &mut session_info, &mut count) } {
+        return Err(format!("Unable to enumerate sessions - {}", e.message()).into());
+    }
+    if session_info.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let entries = unsafe { slice::from_raw_parts(session_info, count as usize) };
+    let sessions = entries.iter().map(|entry| {
+        let session_id = entry.SessionId;
+        let has_user = query_session_string(session_id, WTSUserName).is_some_and(|username| !username.is_empty());
+        let user = if has_user {
+            get_session_user(session_id)
+                .inspect_err(|e| warn!(action="current_sessions_info", "Unable to get the user for session_id {} - {}", session_id, e))
+                .ok()
+        } else {
+            None
+        };
+        SessionInfo {
+            session_id,
+            station_name: unsafe { entry.pWinStationName.to_string() }.unwrap_or_default(),
+            connect_state: connect_state(entry.State),
+            client_name: query_session_string(session_id, WTSClientName).filter(|client_name| !client_name.is_empty()),
+            client_address: query_client_address(session_id),
+            user,
+        }
+    }).collect();
+
+    unsafe { WTSFreeMemory(session_info as *mut core::ffi::c_void) };
+    Ok(sessions)
+}
+
+#[allow(non_upper_case_globals)]
+fn connect_state(state: WTS_CONNECTSTATE_CLASS) -> ConnectState {
+    match state {
+        WTSActive => ConnectState::Active,
+        WTSConnected => ConnectState::Connected,
+        WTSConnectQuery => ConnectState::ConnectQuery,
+        WTSShadow => ConnectState::Shadow,
+        WTSDisconnected => ConnectState::Disconnected,
+        WTSIdle => ConnectState::Idle,
+        WTSListen => ConnectState::Listen,
+        WTSReset => ConnectState::Reset,
+        WTSDown => ConnectState::Down,
+        WTSInit => ConnectState::Init,
+        _ => ConnectState::Unknown
+    }
+}
+
+/// Buffer returned by `WTSQuerySessionInformationW`, freed on drop.
+struct WtsBuffer {
+    inner: PWSTR,
+    len: u32,
+}
+
+impl Drop for WtsBuffer {
+    fn drop(&mut self) {
+        if !self.inner.is_null() {
+            unsafe { WTSFreeMemory(self.inner.0 as *mut core::ffi::c_void) };
+        }
+    }
+}
+
+fn query_session_information(session_id: u32, info_class: WTS_INFO_CLASS) -> Option<WtsBuffer> {
+    let mut buffer = WtsBuffer { inner: PWSTR::null(), len: 0 };
+    unsafe { WTSQuerySessionInformationW(Some(WTS_CURRENT_SERVER_HANDLE), session_id, info_class, &mut buffer.inner, &mut buffer.len) }.ok()?;
+    (!buffer.inner.is_null()).then_some(buffer)
+}
+
+fn query_session_string(session_id: u32, info_class: WTS_INFO_CLASS) -> Option<String> {
+    let buffer = query_session_information(session_id, info_class)?;
+    unsafe { buffer.inner.to_string() }.ok()
+}
+
+/// The client's IP address, None for the console and sessions without a network client.
+fn query_client_address(session_id: u32) -> Option<String> {
+    let buffer = query_session_information(session_id, WTSClientAddress)?;
+    if (buffer.len as usize) < std::mem::size_of::<WTS_CLIENT_ADDRESS>() {
+        return None;
+    }
+    let client_address = unsafe { &*(buffer.inner.0 as *const WTS_CLIENT_ADDRESS) };
+    let address = &client_address.Address;
+    match client_address.AddressFamily {
+        AF_INET => Some(Ipv4Addr::new(address[2], address[3], address[4], address[5]).to_string()),
+        AF_INET6 => {
+            let octets: [u8; 16] = address[2..18].try_into().ok()?;
+            Some(Ipv6Addr::from(octets).to_string())
+        },
+        _ => None
+    }
+}
+
+/// The session notifications that are recorded, None for the rest.
+pub fn session_change(param: SessionChangeParam) -> Option<SessionChange> {
+    let change_type = match param.reason {
+        SessionChangeReason::SessionLogon => SessionChangeType::Logon,
+        SessionChangeReason::SessionLogoff => SessionChangeType::Logoff,
+        SessionChangeReason::SessionLock => SessionChangeType::Lock,
+        SessionChangeReason::SessionUnlock => SessionChangeType::Unlock,
+        SessionChangeReason::RemoteConnect => SessionChangeType::RemoteConnect,
+        SessionChangeReason::RemoteDisconnect => SessionChangeType::RemoteDisconnect,
+        _ => return None
+    };
+    Some(SessionChange { change_type, session_id: param.notification.session_id })
+}